@@ -12,6 +12,11 @@ pub fn generate<T: Service>(service: &T, config: &Builder) -> TokenStream {
     let service_ident = quote::format_ident!("{}Client", service.name());
     let client_mod = quote::format_ident!("{}_client", naive_snake_case(service.name()));
     let methods = generate_methods(service, config);
+    let request_client = if config.send_futures {
+        quote!(::prpc::client::SendRequestClient)
+    } else {
+        quote!(::prpc::client::RequestClient)
+    };
 
     let service_doc = generate_doc_comments(service.comment());
     let mod_attributes = attributes.for_mod(service.package());
@@ -30,12 +35,22 @@ pub fn generate<T: Service>(service: &T, config: &Builder) -> TokenStream {
 
             impl<Client> #service_ident<Client>
             where
-                Client: ::prpc::client::RequestClient
+                Client: #request_client
             {
                 pub fn new(client: Client) -> Self {
                     Self { client }
                 }
 
+                /// A client sending its requests with `timeout`, if any, e.g. the
+                /// `Context::remaining` time of the request being handled, so that its deadline
+                /// propagates to the called service.
+                pub fn with_timeout(
+                    &self,
+                    timeout: Option<::core::time::Duration>,
+                ) -> #service_ident<::prpc::client::WithTimeout<&Client>> {
+                    #service_ident::new(::prpc::client::WithTimeout::new(&self.client, timeout))
+                }
+
                 #methods
             }
         }
@@ -44,6 +59,7 @@ pub fn generate<T: Service>(service: &T, config: &Builder) -> TokenStream {
 
 fn generate_methods<T: Service>(service: &T, config: &Builder) -> TokenStream {
     let mut stream = TokenStream::new();
+    let service_name = crate::join_path(config, service.package(), service.identifier(), "");
     for method in service.methods() {
         let path = crate::join_path(
             config,
@@ -55,9 +71,16 @@ fn generate_methods<T: Service>(service: &T, config: &Builder) -> TokenStream {
         stream.extend(generate_doc_comments(method.comment()));
 
         let method = match (method.client_streaming(), method.server_streaming()) {
-            (false, false) => generate_unary(method, config, path),
-            _ => {
-                panic!("Only unary method supported");
+            (false, false) if config.scale_encoding => {
+                let mut unary = generate_unary(method, config, &service_name, path.clone());
+                unary.extend(generate_doc_comments(method.comment()));
+                unary.extend(generate_unary_scale(method, config, &service_name, path));
+                unary
+            }
+            (false, false) => generate_unary(method, config, &service_name, path),
+            (false, true) => generate_server_streaming(method, config, &service_name, path),
+            (true, server_streaming) => {
+                generate_client_streaming(method, config, &service_name, path, server_streaming)
             }
         };
 
@@ -67,12 +90,88 @@ fn generate_methods<T: Service>(service: &T, config: &Builder) -> TokenStream {
     stream
 }
 
-fn generate_unary<T: Method>(method: &T, config: &Builder, path: String) -> TokenStream {
+// Await the `call` of the transport, in a tracing span if enabled.
+fn generate_call(
+    config: &Builder,
+    service_name: &str,
+    path: &str,
+    request_size: TokenStream,
+    call: TokenStream,
+) -> TokenStream {
+    if !config.tracing {
+        return quote!(#call.await);
+    }
+    quote! {
+        ::prpc::trace::request(
+            ::prpc::trace::request_span(#service_name, #path, #request_size),
+            #call,
+        )
+        .await
+    }
+}
+
+// The type of the response streams of the transport, `Send` with `Builder::send_futures`.
+fn response_stream(config: &Builder) -> TokenStream {
+    if config.send_futures {
+        quote!(::prpc::client::SendResponseStream)
+    } else {
+        quote!(::prpc::client::ResponseStream)
+    }
+}
+
+fn generate_unary<T: Method>(
+    method: &T,
+    config: &Builder,
+    service_name: &str,
+    path: String,
+) -> TokenStream {
     let ident = format_ident!("{}", method.name());
     let (request, response) =
         method.request_response_name(&config.proto_path, config.compile_well_known_types);
+    let call = generate_call(
+        config,
+        service_name,
+        &path,
+        quote!(Some(::prpc::Message::encoded_len(&request))),
+        quote!(self.client.request(#path, request)),
+    );
+
+    template_quote::quote! {
+        pub async fn #ident(
+            &self
+            #(if request.is_some())
+            {
+                , request: #request,
+            }
+        ) -> Result<#response, ::prpc::client::Error> {
+            #(if request.is_none())
+            {
+                let request = ();
+            }
+            #call
+        }
+    }
+}
+
+fn generate_unary_scale<T: Method>(
+    method: &T,
+    config: &Builder,
+    service_name: &str,
+    path: String,
+) -> TokenStream {
+    let ident = format_ident!("{}_scale", method.name());
+    let (request, response) =
+        method.request_response_name(&config.proto_path, config.compile_well_known_types);
+    let call = generate_call(
+        config,
+        service_name,
+        &path,
+        quote!(Some(::prpc::codec::scale::Encode::encoded_size(&request))),
+        quote!(self.client.request_scale(#path, request)),
+    );
 
     template_quote::quote! {
+        /// SCALE encoded variant of this method.
         pub async fn #ident(
             &self
             #(if request.is_some())
@@ -84,7 +183,85 @@ fn generate_unary<T: Method>(method: &T, config: &Builder, path: String) -> Toke
             {
                 let request = ();
             }
-            self.client.request(#path, request).await
+            #call
+        }
+    }
+}
+
+fn generate_server_streaming<T: Method>(
+    method: &T,
+    config: &Builder,
+    service_name: &str,
+    path: String,
+) -> TokenStream {
+    let ident = format_ident!("{}", method.name());
+    let (request, response) =
+        method.request_response_name(&config.proto_path, config.compile_well_known_types);
+    let call = generate_call(
+        config,
+        service_name,
+        &path,
+        quote!(Some(::prpc::Message::encoded_len(&request))),
+        quote!(self.client.request_stream(#path, request)),
+    );
+    let response_stream = response_stream(config);
+
+    template_quote::quote! {
+        pub async fn #ident(
+            &self
+            #(if request.is_some())
+            {
+                , request: #request,
+            }
+        ) -> Result<#response_stream<'_, #response>, ::prpc::client::Error> {
+            #(if request.is_none())
+            {
+                let request = ();
+            }
+            #call
+        }
+    }
+}
+
+fn generate_client_streaming<T: Method>(
+    method: &T,
+    config: &Builder,
+    service_name: &str,
+    path: String,
+    server_streaming: bool,
+) -> TokenStream {
+    let ident = format_ident!("{}", method.name());
+    let (request, response) =
+        method.request_response_name(&config.proto_path, config.compile_well_known_types);
+    let request = request.unwrap_or_else(|| quote!(()));
+    let call = generate_call(
+        config,
+        service_name,
+        &path,
+        quote!(None),
+        quote!(self.client.request_client_stream(#path, requests)),
+    );
+    let response_stream = response_stream(config);
+    let send = config.send_futures.then(|| quote!(+ Send));
+
+    if server_streaming {
+        quote! {
+            pub async fn #ident<'a>(
+                &'a self,
+                requests: impl ::prpc::Stream<Item = #request> #send + 'a,
+            ) -> Result<#response_stream<'a, #response>, ::prpc::client::Error> {
+                #call
+            }
+        }
+    } else {
+        quote! {
+            pub async fn #ident(
+                &self,
+                requests: impl ::prpc::Stream<Item = #request> #send,
+            ) -> Result<#response, ::prpc::client::Error> {
+                let responses = #call?;
+                ::prpc::client::single_response(responses).await
+            }
         }
     }
 }
@@ -69,6 +69,24 @@ pub trait Method {
     ) -> (Option<TokenStream>, TokenStream);
 }
 
+/// Receiver of the methods of the generated `{Service}Rpc` traits, see
+/// [`Builder::handler_receiver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Receiver {
+    /// `self`: the generated server owns the handler and consumes it on every call, so the
+    /// handler is built, e.g. cloned, for each request.
+    Value,
+    /// `&self`: the generated server shares the handler in an `Arc`.
+    ///
+    /// The response streams of streaming methods can not borrow the handler, their methods
+    /// take a lifetime `'a` bounding the `&'a Context` and request stream arguments and the
+    /// returned `impl Stream + 'a`.
+    Ref,
+    /// `self: Arc<Self>`: the generated server shares the handler in an `Arc` and passes a clone
+    /// of it to every call.
+    Arc,
+}
+
 /// Attributes that will be added to `mod` and `struct` items.
 #[derive(Debug, Default, Clone)]
 pub struct Attributes {
@@ -234,6 +252,8 @@ fn naive_snake_case(name: &str) -> String {
     s
 }
 
+// Keep in sync with `prpc::server::MethodPaths::join`, used by the services built from
+// descriptors at runtime.
 fn join_path(config: &Builder, package: &str, service: &str, method: &str) -> String {
     let mut parts = vec![];
     if config.emit_package {
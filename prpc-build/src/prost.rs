@@ -1,4 +1,4 @@
-use super::{client, server, Attributes};
+use super::{client, server, Attributes, Receiver};
 use proc_macro2::TokenStream;
 use prost_build::{Config, Method, Service};
 use quote::ToTokens;
@@ -14,6 +14,14 @@ pub fn configure() -> Builder {
         build_client: true,
         build_server: true,
         build_scale_ext: true,
+        scale_encoding: false,
+        tracing: false,
+        handler_context: false,
+        handler_receiver: Receiver::Value,
+        send_futures: false,
+        associated_error_type: false,
+        error_type: None,
+        dyn_handlers: false,
         out_dir: None,
         extern_path: Vec::new(),
         field_attributes: Vec::new(),
@@ -201,6 +209,14 @@ pub struct Builder {
     pub(crate) build_client: bool,
     pub(crate) build_server: bool,
     pub(crate) build_scale_ext: bool,
+    pub(crate) scale_encoding: bool,
+    pub(crate) tracing: bool,
+    pub(crate) handler_context: bool,
+    pub(crate) handler_receiver: Receiver,
+    pub(crate) send_futures: bool,
+    pub(crate) associated_error_type: bool,
+    pub(crate) error_type: Option<String>,
+    pub(crate) dyn_handlers: bool,
     pub(crate) extern_path: Vec<(String, String)>,
     pub(crate) field_attributes: Vec<(String, String)>,
     pub(crate) type_attributes: Vec<(String, String)>,
@@ -239,6 +255,81 @@ impl Builder {
         self
     }
 
+    /// Pass the request `::prpc::server::Context` to every generated handler method.
+    ///
+    /// The methods of the generated `{Service}Rpc` traits then take a `&Context` argument
+    /// right after the receiver.
+    pub fn handler_context(mut self, enable: bool) -> Self {
+        self.handler_context = enable;
+        self
+    }
+
+    /// Set the receiver of the methods of the generated `{Service}Rpc` traits, [`Receiver::Value`]
+    /// by default.
+    ///
+    /// With [`Receiver::Ref`] or [`Receiver::Arc`] the generated `{Service}Server` holds the
+    /// handler in an `Arc`, so it is cheaply cloned and long-lived state is shared by all calls
+    /// instead of being rebuilt for each of them. The server is then also built `From<Arc<T>>`,
+    /// e.g. to compose services sharing one `Arc` of the app state.
+    pub fn handler_receiver(mut self, receiver: Receiver) -> Self {
+        self.handler_receiver = receiver;
+        self
+    }
+
+    /// Generate `Send` futures, so calls can be spawned on multi-threaded runtimes.
+    ///
+    /// The methods of the generated `{Service}Rpc` traits then return
+    /// `impl Future<Output = ...> + Send` and `impl Stream + Send` response streams, and the
+    /// traits require `Send + Sync` and `prpc::server::SendAuthorizer` instead of `Authorizer`.
+    /// The generated servers also implement `prpc::server::SendService`, and the generated
+    /// clients require a `prpc::client::SendRequestClient` transport.
+    ///
+    /// Only unary and server-streaming calls are `Send`. Client-streaming and bidirectional
+    /// methods keep their non-`Send` futures, as the request streams dispatched by
+    /// `prpc::server::Service` are not `Send`, so they must be dispatched on a local task, e.g.
+    /// with `tokio::task::spawn_local`. Neither do `prpc::server::MiddlewareService` and
+    /// `prpc::server::ServiceRegistry` implement `SendService`, even over servers generated with
+    /// this option.
+    pub fn send_futures(mut self, enable: bool) -> Self {
+        self.send_futures = enable;
+        self
+    }
+
+    /// Give the generated `{Service}Rpc` traits an associated
+    /// `type Error: Into<::prpc::server::ProtoError>` returned by their methods, instead of
+    /// `anyhow::Error`.
+    ///
+    /// The `ProtoError` of a failed call is sent to the caller as is, so handlers choose the
+    /// status code and message of their domain errors with the `Into` impl. Takes precedence over
+    /// [`Builder::error_type`].
+    pub fn associated_error_type(mut self, enable: bool) -> Self {
+        self.associated_error_type = enable;
+        self
+    }
+
+    /// Return the error type at `path`, e.g. `crate::Error`, from the methods of all generated
+    /// `{Service}Rpc` traits instead of `anyhow::Error`. The type must implement
+    /// `Into<::prpc::server::ProtoError>`, as with [`Builder::associated_error_type`].
+    pub fn error_type(mut self, path: impl Into<String>) -> Self {
+        self.error_type = Some(path.into());
+        self
+    }
+
+    /// Also generate an object safe `Dyn{Service}Rpc` trait for each `{Service}Rpc` trait, with
+    /// boxed futures and streams, to serve handlers chosen at runtime.
+    ///
+    /// Every handler implements its `Dyn{Service}Rpc` trait. With [`Receiver::Value`] the
+    /// handlers must be `Clone` and `Box<dyn Dyn{Service}Rpc>` and `Arc<dyn Dyn{Service}Rpc>`
+    /// implement `{Service}Rpc`; with
+    /// [`Receiver::Ref`] and [`Receiver::Arc`] the servers are built from an
+    /// `Arc<dyn Dyn{Service}Rpc>`. The object safe forms of `prpc::server::Service` and
+    /// `prpc::client::RequestClient` are `DynService` and `DynRequestClient`, or
+    /// `SendDynService` and `SendDynRequestClient` with [`Builder::send_futures`].
+    pub fn dyn_handlers(mut self, enable: bool) -> Self {
+        self.dyn_handlers = enable;
+        self
+    }
+
     /// Enable the output to be formated by rustfmt.
     pub fn format(mut self, run: bool) -> Self {
         self.format = run;
@@ -478,6 +569,33 @@ impl Builder {
             .field_attribute(".", "#[serde(default)]")
     }
 
+    /// Enable SCALE as a wire encoding of requests and responses.
+    ///
+    /// This derives `Encode` and `Decode` of `::prpc::codec::scale` for all generated types,
+    /// dispatches `Encoding::Scale` requests in the generated servers, and adds a `{method}_scale`
+    /// variant of every unary method to the generated clients.
+    ///
+    /// All field types must support SCALE, so messages can not contain `float`/`double` fields
+    /// or well known types, and map fields must be generated as `BTreeMap`, e.g. by passing a
+    /// `prost_build::Config` with `btree_map(["."])` to [`Builder::compile_with_config`].
+    pub fn enable_scale_encoding(self) -> Self {
+        let mut builder = self
+            .type_attribute(
+                ".",
+                "#[derive(::prpc::codec::scale::Encode, ::prpc::codec::scale::Decode)]",
+            )
+            .type_attribute(".", "#[codec(crate = ::prpc::codec::scale)]");
+        builder.scale_encoding = true;
+        builder
+    }
+
+    /// Run every call of the generated servers and clients in a `tracing` span and record failed
+    /// calls as events, see `prpc::trace`. Requires the `tracing` feature of `prpc`.
+    pub fn enable_tracing(mut self) -> Self {
+        self.tracing = true;
+        self
+    }
+
     /// Compile all .proto files in the specified directory.
     /// The include directory will be the same as the proto directory.
     pub fn compile_dir(self, proto_dir: impl AsRef<Path>) -> io::Result<()> {
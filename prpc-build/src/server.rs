@@ -1,7 +1,7 @@
 use super::{Method, Service};
-use crate::{generate_doc_comment, generate_doc_comments, naive_snake_case, Builder};
+use crate::{generate_doc_comment, generate_doc_comments, naive_snake_case, Builder, Receiver};
 use proc_macro2::{Span, TokenStream};
-use quote::quote;
+use quote::{quote, ToTokens};
 use syn::{Ident, Lit, LitStr};
 
 /// Generate service for Server.
@@ -10,19 +10,68 @@ use syn::{Ident, Lit, LitStr};
 /// a public module containing the server service and handler trait.
 pub fn generate<T: Service>(service: &T, config: &Builder) -> TokenStream {
     let attributes = &config.server_attributes;
-    let methods = generate_methods(service, config, false);
-    let json_methods = generate_methods(service, config, true);
+    let methods = generate_methods(service, config, DispatchKind::Unary);
+    let stream_methods = generate_methods(service, config, DispatchKind::ServerStreaming);
+    let client_stream_methods = generate_methods(service, config, DispatchKind::ClientStreaming);
 
     let server_service = quote::format_ident!("{}Server", service.name());
     let server_trait = quote::format_ident!("{}Rpc", service.name());
     let server_mod = quote::format_ident!("{}_server", naive_snake_case(service.name()));
-    let service_name = Lit::Str(LitStr::new(service.name(), Span::call_site()));
+    let service_path = crate::join_path(config, service.package(), service.identifier(), "");
+    let service_name = Lit::Str(LitStr::new(&service_path, Span::call_site()));
+    let dispatch = generate_dispatch(
+        config,
+        &service_name,
+        quote!(Some(_data.as_ref().len())),
+        quote!(dispatch),
+        methods,
+    );
+    let (response_stream, trace_stream) = if config.send_futures {
+        (
+            quote!(::prpc::server::SendResponseStream),
+            quote!(dispatch_send_stream),
+        )
+    } else {
+        (
+            quote!(::prpc::server::ResponseStream),
+            quote!(dispatch_stream),
+        )
+    };
+    let stream_dispatch = generate_dispatch(
+        config,
+        &service_name,
+        quote!(Some(_data.as_ref().len())),
+        trace_stream,
+        stream_methods,
+    );
+    let client_stream_dispatch = generate_dispatch(
+        config,
+        &service_name,
+        quote!(None),
+        quote!(dispatch_stream),
+        client_stream_methods,
+    );
     let supported_methods = generate_supported_methods(service, config);
+    let method_timeouts = generate_method_timeouts(service, config);
     let method_enum = generate_methods_enum(service, config);
     let generated_trait = generate_trait(service, config, server_trait.clone());
+    let dyn_trait = generate_dyn_trait(service, config, &server_trait);
+    let stream_response = if config.send_futures {
+        quote!(Ok(self
+            .dispatch_stream_request(context, path, data, encoding)
+            .await?))
+    } else {
+        quote!(
+            self.dispatch_stream_request(context, path, data, encoding)
+                .await
+        )
+    };
+    let send_service = generate_send_service(config, &server_service, &server_trait);
     let service_doc = generate_doc_comments(service.comment());
     let mod_attributes = attributes.for_mod(service.package());
     let struct_attributes = attributes.for_struct(service.identifier());
+    let server = generate_server(config, &server_service, &server_trait);
+    let server_bound = server_bound(config, &server_trait);
 
     quote! {
         /// Generated server implementations.
@@ -34,114 +83,626 @@ pub fn generate<T: Service>(service: &T, config: &Builder) -> TokenStream {
 
             #generated_trait
 
+            #dyn_trait
+
             #service_doc
             #(#struct_attributes)*
-            #[derive(Debug)]
-            pub struct #server_service<T: #server_trait> {
-                inner: T,
-            }
-
-            impl<T: #server_trait> #server_service<T> {
-                pub fn new(inner: T) -> Self {
-                    Self {
-                        inner,
-                    }
+            #server
+            impl<T: #server_bound> #server_service<T> {
+                /// Limit the requests of all methods, unless overridden by `@limit` annotations.
+                pub fn with_limits(mut self, limits: ::prpc::server::Limits) -> Self {
+                    self.limits = limits;
+                    self
                 }
 
-                pub async fn dispatch_request(self, path: &str, _data: impl AsRef<[u8]>) -> Result<Vec<u8>, ::prpc::server::Error> {
+                pub async fn dispatch_request(self, _context: &::prpc::server::Context, path: &str, _data: impl AsRef<[u8]>, _encoding: ::prpc::server::Encoding) -> Result<Vec<u8>, ::prpc::server::DispatchError> {
                     #![allow(clippy::let_unit_value)]
-                    match path {
-                        #methods
-                        _ => anyhow::bail!("Service not found: {path}"),
-                    }
+                    #dispatch
                 }
 
-                pub async fn dispatch_json_request(self, path: &str, _data: impl AsRef<[u8]>, _query: bool) -> Result<Vec<u8>, ::prpc::server::Error> {
+                pub async fn dispatch_stream_request<'a>(self, _context: &'a ::prpc::server::Context, path: &str, _data: impl AsRef<[u8]>, _encoding: ::prpc::server::Encoding) -> Result<#response_stream<'a>, ::prpc::server::DispatchError>
+                where
+                    T: 'a,
+                {
                     #![allow(clippy::let_unit_value)]
-                    match path {
-                        #json_methods
-                        _ => anyhow::bail!("Service not found: {path}"),
-                    }
+                    #stream_dispatch
+                }
+
+                pub async fn dispatch_client_stream_request<'a>(self, _context: &'a ::prpc::server::Context, path: &str, _requests: impl ::prpc::Stream<Item = Result<Vec<u8>, ::prpc::server::Error>> + 'a, _encoding: ::prpc::server::Encoding) -> Result<::prpc::server::ResponseStream<'a>, ::prpc::server::DispatchError>
+                where
+                    T: 'a,
+                {
+                    #client_stream_dispatch
                 }
                 #supported_methods
             }
 
-            impl<T: #server_trait> ::prpc::server::NamedService for #server_service<T> {
+            #send_service
+            impl<T: #server_bound> ::prpc::server::NamedService for #server_service<T> {
                 const NAME: &'static str = #service_name;
             }
-            impl<T: #server_trait> ::prpc::server::Service for #server_service<T> {
+            impl<T: #server_bound> ::prpc::server::Service for #server_service<T> {
                 type Methods = &'static [&'static str];
                 fn methods() -> Self::Methods {
                     Self::supported_methods()
                 }
-                async fn dispatch_request(self, path: &str, data: impl AsRef<[u8]>, json: bool, query: bool) -> Result<Vec<u8>, ::prpc::server::Error> {
-                    if json {
-                        self.dispatch_json_request(path, data, query).await
-                    } else {
-                        self.dispatch_request(path, data).await
+                #method_timeouts
+                async fn dispatch_request(self, context: &::prpc::server::Context, path: &str, data: impl AsRef<[u8]>, encoding: ::prpc::server::Encoding) -> Result<Vec<u8>, ::prpc::server::DispatchError> {
+                    self.dispatch_request(context, path, data, encoding).await
+                }
+                async fn dispatch_stream_request<'a>(self, context: &'a ::prpc::server::Context, path: &str, data: impl AsRef<[u8]>, encoding: ::prpc::server::Encoding) -> Result<::prpc::server::ResponseStream<'a>, ::prpc::server::DispatchError>
+                where
+                    Self: 'a,
+                {
+                    #stream_response
+                }
+                async fn dispatch_client_stream_request<'a>(self, context: &'a ::prpc::server::Context, path: &str, requests: impl ::prpc::Stream<Item = Result<Vec<u8>, ::prpc::server::Error>> + 'a, encoding: ::prpc::server::Encoding) -> Result<::prpc::server::ResponseStream<'a>, ::prpc::server::DispatchError>
+                where
+                    Self: 'a,
+                {
+                    self.dispatch_client_stream_request(context, path, requests, encoding).await
+                }
+            }
+        }
+    }
+}
+
+// The `SendService` impl of the server, with `Builder::send_futures`.
+fn generate_send_service(
+    config: &Builder,
+    server_service: &Ident,
+    server_trait: &Ident,
+) -> Option<TokenStream> {
+    if !config.send_futures {
+        return None;
+    }
+    let server_bound = server_bound(config, server_trait);
+    Some(quote! {
+        impl<T: #server_bound> ::prpc::server::SendService for #server_service<T> {
+            fn dispatch_request(self, context: &::prpc::server::Context, path: &str, data: impl AsRef<[u8]> + Send, encoding: ::prpc::server::Encoding) -> impl ::core::future::Future<Output = Result<Vec<u8>, ::prpc::server::DispatchError>> + Send {
+                self.dispatch_request(context, path, data, encoding)
+            }
+            fn dispatch_stream_request<'a>(self, context: &'a ::prpc::server::Context, path: &str, data: impl AsRef<[u8]> + Send, encoding: ::prpc::server::Encoding) -> impl ::core::future::Future<Output = Result<::prpc::server::SendResponseStream<'a>, ::prpc::server::DispatchError>> + Send
+            where
+                Self: 'a,
+            {
+                self.dispatch_stream_request(context, path, data, encoding)
+            }
+        }
+    })
+}
+
+// The server struct holding the handler, with its constructors.
+fn generate_server(config: &Builder, server_service: &Ident, server_trait: &Ident) -> TokenStream {
+    if config.handler_receiver == Receiver::Value {
+        return quote! {
+            #[derive(Debug, Clone)]
+            pub struct #server_service<T: #server_trait> {
+                inner: T,
+                limits: ::prpc::server::Limits,
+            }
+
+            impl<T: #server_trait> #server_service<T> {
+                pub fn new(inner: T) -> Self {
+                    Self {
+                        inner,
+                        limits: ::prpc::server::Limits::new(),
                     }
                 }
             }
+
             impl<T: #server_trait> From<T> for #server_service<T> {
                 fn from(inner: T) -> Self {
                     Self::new(inner)
                 }
             }
+        };
+    }
+    quote! {
+        #[derive(Debug)]
+        pub struct #server_service<T: #server_trait + ?Sized> {
+            inner: alloc::sync::Arc<T>,
+            limits: ::prpc::server::Limits,
+        }
+
+        impl<T: #server_trait + ?Sized> #server_service<T> {
+            pub fn new(inner: T) -> Self
+            where
+                T: Sized,
+            {
+                Self::from_arc(alloc::sync::Arc::new(inner))
+            }
+
+            /// Serve a handler shared with other servers or with the rest of the app.
+            pub fn from_arc(inner: alloc::sync::Arc<T>) -> Self {
+                Self {
+                    inner,
+                    limits: ::prpc::server::Limits::new(),
+                }
+            }
+        }
+
+        impl<T: #server_trait + ?Sized> Clone for #server_service<T> {
+            fn clone(&self) -> Self {
+                Self {
+                    inner: self.inner.clone(),
+                    limits: self.limits,
+                }
+            }
+        }
+
+        impl<T: #server_trait> From<T> for #server_service<T> {
+            fn from(inner: T) -> Self {
+                Self::new(inner)
+            }
+        }
+
+        impl<T: #server_trait + ?Sized> From<alloc::sync::Arc<T>> for #server_service<T> {
+            fn from(inner: alloc::sync::Arc<T>) -> Self {
+                Self::from_arc(inner)
+            }
         }
     }
 }
 
+// The bound on the handler of the server, which is unsized for shared handlers so that it can be
+// an `Arc<dyn Dyn{Service}Rpc>`.
+fn server_bound(config: &Builder, server_trait: &Ident) -> TokenStream {
+    match config.handler_receiver {
+        Receiver::Value => quote!(#server_trait),
+        Receiver::Ref | Receiver::Arc => quote!(#server_trait + ?Sized),
+    }
+}
+
+// Match the method `path` to the dispatch `methods`, in a tracing span if enabled.
+fn generate_dispatch(
+    config: &Builder,
+    service_name: &Lit,
+    request_size: TokenStream,
+    trace_fn: TokenStream,
+    methods: TokenStream,
+) -> TokenStream {
+    let dispatch = quote! {
+        match path {
+            #methods
+            _ => Err(::prpc::server::DispatchError::MethodNotFound(path.into())),
+        }
+    };
+    if !config.tracing {
+        return dispatch;
+    }
+    quote! {
+        let span = ::prpc::trace::dispatch_span(#service_name, path, _context, _encoding, #request_size);
+        ::prpc::trace::#trace_fn(span, async move { #dispatch }).await
+    }
+}
+
 fn generate_trait<T: Service>(service: &T, config: &Builder, server_trait: Ident) -> TokenStream {
-    let methods =
-        generate_trait_methods(service, &config.proto_path, config.compile_well_known_types);
+    let methods = generate_trait_methods(service, config);
     let trait_doc = generate_doc_comment(format!(
         "Generated trait containing RPC methods that should be implemented for use with {}Server.",
         service.name()
     ));
 
+    let authorizer = service
+        .methods()
+        .iter()
+        .any(|method| !auth_policies(method).is_empty())
+        .then(|| generate_authorizer_trait(config));
+    let supertraits = match (authorizer, config.send_futures) {
+        (Some(authorizer), true) => quote!(: #authorizer + Send + Sync),
+        (Some(authorizer), false) => quote!(: #authorizer),
+        (None, true) => quote!(: Send + Sync),
+        (None, false) => quote!(),
+    };
+
+    let error_type = config.associated_error_type.then(|| {
+        quote! {
+            /// The error returned by the methods, sent to the caller as its `ProtoError`.
+            type Error: Into<::prpc::server::ProtoError>;
+        }
+    });
+
     quote! {
         #trait_doc
-        pub trait #server_trait {
+        pub trait #server_trait #supertraits {
+            #error_type
             #methods
         }
     }
 }
 
-fn generate_trait_methods<T: Service>(
+// The object safe `Dyn{Service}Rpc` trait with `Builder::dyn_handlers`, implemented for every
+// handler, and the `{Service}Rpc` impl of its trait objects.
+fn generate_dyn_trait<T: Service>(
     service: &T,
-    proto_path: &str,
-    compile_well_known_types: bool,
-) -> TokenStream {
-    let mut stream = TokenStream::new();
+    config: &Builder,
+    server_trait: &Ident,
+) -> Option<TokenStream> {
+    if !config.dyn_handlers {
+        return None;
+    }
+    let dyn_trait = quote::format_ident!("Dyn{}Rpc", service.name());
+    let receiver = config.handler_receiver;
+    let trait_doc = match receiver {
+        Receiver::Value => format!(
+            "Object safe form of [`{server_trait}`], implemented for every `Clone` handler. \
+             `Box<dyn {dyn_trait}>` and `Arc<dyn {dyn_trait}>` implement [`{server_trait}`], \
+             the latter being `Clone` as required to register the server as a service."
+        ),
+        Receiver::Ref | Receiver::Arc => format!(
+            "Object safe form of [`{server_trait}`], implemented for every handler. \
+             `dyn {dyn_trait}` implements [`{server_trait}`], so a server can be built from an \
+             `Arc<dyn {dyn_trait}>`."
+        ),
+    };
+    let trait_doc = generate_doc_comment(trait_doc);
+
+    // `Self::Error` is ambiguous in the impls, as the types implement both traits.
+    let error = handler_error_type(config);
+    let (handler_error, object_error) = if config.associated_error_type {
+        (quote!(<T as #server_trait>::Error), quote!(E))
+    } else {
+        (error.clone(), error.clone())
+    };
+    let (error_type, object_generics, object_error_type, handler_error_type) =
+        if config.associated_error_type {
+            (
+                Some({
+                    let doc = generate_doc_comment(format!(
+                        "The error returned by the methods, see [`{server_trait}::Error`]."
+                    ));
+                    quote! {
+                        #doc
+                        type Error: Into<::prpc::server::ProtoError>;
+                    }
+                }),
+                Some(quote!(<E: Into<::prpc::server::ProtoError> + 'static>)),
+                Some(quote!(
+                    type Error = E;
+                )),
+                Some(quote!(type Error = #handler_error;)),
+            )
+        } else {
+            (None, None, None, None)
+        };
+    let object_types = {
+        let assoc = config.associated_error_type.then(|| quote!(<Error = E>));
+        match receiver {
+            Receiver::Value => vec![
+                quote!(alloc::boxed::Box<dyn #dyn_trait #assoc>),
+                quote!(alloc::sync::Arc<dyn #dyn_trait #assoc>),
+            ],
+            Receiver::Ref | Receiver::Arc => vec![quote!(dyn #dyn_trait #assoc)],
+        }
+    };
+    let (handler_bound, handler, object) = match receiver {
+        Receiver::Value => (quote!(#server_trait + Clone), quote!(this), quote!(&*self)),
+        Receiver::Ref | Receiver::Arc => (quote!(#server_trait), quote!(self), quote!(self)),
+    };
+    let clone = (receiver == Receiver::Value).then(|| quote!(let this = self.clone();));
 
+    let mut methods = TokenStream::new();
+    let mut handler_methods = TokenStream::new();
+    let mut object_methods = TokenStream::new();
     for method in service.methods() {
         let name = quote::format_ident!("{}", method.name());
+        let method_doc = generate_doc_comments(method.comment());
+        let signature = generate_dyn_method_signature(method, config, &error);
+        methods.extend(quote! {
+            #method_doc
+            #signature;
+        });
 
         let (req_message, res_message) =
-            method.request_response_name(proto_path, compile_well_known_types);
+            method.request_response_name(&config.proto_path, config.compile_well_known_types);
+        let context = config.handler_context.then(|| quote!(context,));
+        let args = if method.client_streaming() {
+            quote!(#context requests)
+        } else if req_message.is_some() {
+            quote!(#context request)
+        } else {
+            quote!(#context)
+        };
 
-        let method_doc = generate_doc_comments(method.comment());
+        let signature = generate_dyn_method_signature(method, config, &handler_error);
+        let call = quote!(#server_trait::#name(#handler, #args));
+        let body = if method.server_streaming() {
+            let stream = dyn_stream_type(method, config);
+            quote! {
+                alloc::boxed::Box::pin(async move {
+                    let stream = #call.await?;
+                    let stream: #stream<'a, Result<#res_message, #handler_error>> = alloc::boxed::Box::pin(stream);
+                    Ok(stream)
+                })
+            }
+        } else {
+            quote!(alloc::boxed::Box::pin(#call))
+        };
+        handler_methods.extend(quote! {
+            #signature {
+                #clone
+                #body
+            }
+        });
 
-        let method = match (method.client_streaming(), method.server_streaming()) {
-            (false, false) => {
-                template_quote::quote! {
-                    #method_doc
-                    async fn #name(self
-                        #(if req_message.is_some()) {
-                            , request: #req_message
-                        }
-                    ) -> ::anyhow::Result<#res_message>;
+        let signature = generate_trait_method_signature(method, config, &object_error, true);
+        object_methods.extend(quote! {
+            #signature {
+                #dyn_trait::#name(#object, #args).await
+            }
+        });
+    }
+
+    let has_auth = service
+        .methods()
+        .iter()
+        .any(|method| !auth_policies(method).is_empty());
+    let mut authorize = None;
+    let mut handler_authorize = None;
+    let mut object_authorizer = None;
+    if has_auth {
+        let future = if config.send_futures {
+            quote!(::prpc::server::SendBoxFuture)
+        } else {
+            quote!(::prpc::server::BoxFuture)
+        };
+        let signature = quote! {
+            fn authorize<'a>(&'a self, context: &'a ::prpc::server::Context, path: &'a str, policy: &'a str)
+                -> #future<'a, Result<(), ::prpc::server::Error>>
+        };
+        authorize = Some(quote! {
+            /// Check the `@auth` policies of the methods, see [`::prpc::server::Authorizer`].
+            #signature;
+        });
+        let authorizer = generate_authorizer_trait(config);
+        handler_authorize = Some(quote! {
+            #signature {
+                alloc::boxed::Box::pin(#authorizer::authorize(self, context, path, policy))
+            }
+        });
+        // The pointers to the trait object implement the trait by forwarding to the object.
+        let assoc = config.associated_error_type.then(|| quote!(<Error = E>));
+        object_authorizer = Some(quote! {
+            impl #object_generics #authorizer for dyn #dyn_trait #assoc {
+                async fn authorize(&self, context: &::prpc::server::Context, path: &str, policy: &str)
+                    -> Result<(), ::prpc::server::Error>
+                {
+                    #dyn_trait::authorize(self, context, path, policy).await
                 }
             }
-            _ => {
-                panic!("Streaming RPC not supported");
+        });
+    }
+    let supertraits = config.send_futures.then(|| quote!(: Send + Sync));
+    let mut object_impls = TokenStream::new();
+    for object_type in &object_types {
+        object_impls.extend(quote! {
+            impl #object_generics #server_trait for #object_type {
+                #object_error_type
+                #object_methods
             }
+        });
+    }
+
+    Some(quote! {
+        #trait_doc
+        pub trait #dyn_trait #supertraits {
+            #error_type
+            #authorize
+            #methods
+        }
+
+        impl<T: #handler_bound> #dyn_trait for T {
+            #handler_error_type
+            #handler_authorize
+            #handler_methods
+        }
+
+        #object_authorizer
+
+        #object_impls
+    })
+}
+
+// The boxed response stream of a method of the `Dyn{Service}Rpc` trait.
+fn dyn_stream_type<T: Method>(method: &T, config: &Builder) -> TokenStream {
+    if send_method(method, config) {
+        quote!(::prpc::server::SendBoxStream)
+    } else {
+        quote!(::prpc::server::BoxStream)
+    }
+}
+
+// The signature of a method of the `Dyn{Service}Rpc` trait, returning `error`.
+fn generate_dyn_method_signature<T: Method>(
+    method: &T,
+    config: &Builder,
+    error: &TokenStream,
+) -> TokenStream {
+    let name = quote::format_ident!("{}", method.name());
+    let (req_message, res_message) =
+        method.request_response_name(&config.proto_path, config.compile_well_known_types);
+
+    let (generics, receiver, future_lifetime, bounds) =
+        match (config.handler_receiver, method.server_streaming()) {
+            (Receiver::Value, _) => (
+                quote!(<'a>),
+                quote!(&self),
+                quote!('a),
+                quote!(where Self: 'a),
+            ),
+            (Receiver::Arc, _) => (
+                quote!(<'a>),
+                quote!(self: alloc::sync::Arc<Self>),
+                quote!('a),
+                quote!(where Self: 'a),
+            ),
+            (Receiver::Ref, false) => (quote!(<'a>), quote!(&'a self), quote!('a), quote!()),
+            // The response stream only borrows the arguments, the future also the handler.
+            (Receiver::Ref, true) => (
+                quote!(<'a, 'b>),
+                quote!(&'b self),
+                quote!('b),
+                quote!(where 'a: 'b),
+            ),
         };
+    let context = config
+        .handler_context
+        .then(|| quote!(, context: &'a ::prpc::server::Context));
+    let request = if method.client_streaming() {
+        let req_message = req_message.unwrap_or_else(|| quote!(()));
+        Some(quote!(, requests: ::prpc::server::RequestStream<'a, #req_message>))
+    } else {
+        req_message.map(|req_message| quote!(, request: #req_message))
+    };
+    let future = if send_method(method, config) {
+        quote!(::prpc::server::SendBoxFuture)
+    } else {
+        quote!(::prpc::server::BoxFuture)
+    };
+    let response = if method.server_streaming() {
+        let stream = dyn_stream_type(method, config);
+        quote!(#stream<'a, Result<#res_message, #error>>)
+    } else {
+        res_message
+    };
+    quote! {
+        fn #name #generics(#receiver #context #request)
+            -> #future<#future_lifetime, Result<#response, #error>>
+            #bounds
+    }
+}
 
-        stream.extend(method);
+// The trait checking the `@auth` annotations, with a `Send` future with `Builder::send_futures`.
+fn generate_authorizer_trait(config: &Builder) -> TokenStream {
+    if config.send_futures {
+        quote!(::prpc::server::SendAuthorizer)
+    } else {
+        quote!(::prpc::server::Authorizer)
     }
+}
 
-    stream
+// Policies of the `@auth <policy>` annotations in the leading comment of a method.
+//
+// A malformed annotation panics rather than being skipped, as skipping it would leave the method
+// without an authorization check.
+fn auth_policies<T: Method>(method: &T) -> Vec<String> {
+    method
+        .comment()
+        .iter()
+        .filter_map(|line| {
+            let line = line.as_ref();
+            let parts: Vec<_> = line.split_whitespace().collect();
+            let invalid = |reason: &str| -> ! {
+                panic!(
+                    "Invalid @auth annotation on {}: {}: {}",
+                    method.name(),
+                    line.trim(),
+                    reason
+                )
+            };
+            match parts[..] {
+                ["@auth", _, ..] => Some(parts[1..].join(" ")),
+                ["@auth"] => invalid("missing policy"),
+                [annotation, ..] if annotation.starts_with("@auth") => {
+                    invalid("expected `@auth <policy>`")
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// The error type returned by the methods of the generated trait.
+fn handler_error_type(config: &Builder) -> TokenStream {
+    if config.associated_error_type {
+        quote!(Self::Error)
+    } else if let Some(path) = &config.error_type {
+        syn::parse_str::<syn::Path>(path)
+            .unwrap_or_else(|_| panic!("Invalid error type {}", path))
+            .to_token_stream()
+    } else {
+        quote!(::anyhow::Error)
+    }
+}
+
+// Whether the handlers return a typed error rather than `anyhow::Error`.
+fn typed_handler_error(config: &Builder) -> bool {
+    config.associated_error_type || config.error_type.is_some()
+}
+
+fn generate_trait_methods<T: Service>(service: &T, config: &Builder) -> TokenStream {
+    let error = handler_error_type(config);
+    service
+        .methods()
+        .iter()
+        .map(|method| {
+            let method_doc = generate_doc_comments(method.comment());
+            let signature = generate_trait_method_signature(method, config, &error, false);
+            quote! {
+                #method_doc
+                #signature;
+            }
+        })
+        .collect()
+}
+
+// The request streams of client-streaming methods are not `Send`, so neither are their futures.
+fn send_method<T: Method>(method: &T, config: &Builder) -> bool {
+    config.send_futures && !method.client_streaming()
+}
+
+// The signature of a method of the generated trait, returning `error`. Implementations are always
+// `async fn`, as only their opaque types capture the lifetimes of the arguments before edition 2024.
+fn generate_trait_method_signature<T: Method>(
+    method: &T,
+    config: &Builder,
+    error: &TokenStream,
+    implementation: bool,
+) -> TokenStream {
+    let receiver = match config.handler_receiver {
+        Receiver::Value => quote!(self),
+        Receiver::Ref => quote!(&self),
+        Receiver::Arc => quote!(self: alloc::sync::Arc<Self>),
+    };
+    let name = quote::format_ident!("{}", method.name());
+
+    let (req_message, res_message) =
+        method.request_response_name(&config.proto_path, config.compile_well_known_types);
+
+    // The response stream of a `&self` method can not borrow the handler, only the arguments. A
+    // stream borrowing both the context and the request stream needs a single lifetime for them.
+    let named_lifetime = config.handler_receiver == Receiver::Ref
+        || (config.handler_context && method.client_streaming());
+    let lifetime = (named_lifetime && method.server_streaming()).then(|| quote!('a));
+    let generics = lifetime.as_ref().map(|lifetime| quote!(<#lifetime>));
+    let arg_lifetime = lifetime.clone().unwrap_or_else(|| quote!('_));
+    let context = config
+        .handler_context
+        .then(|| quote!(, context: &#lifetime ::prpc::server::Context));
+    let request = if method.client_streaming() {
+        let req_message = req_message.unwrap_or_else(|| quote!(()));
+        Some(quote!(, requests: ::prpc::server::RequestStream<#arg_lifetime, #req_message>))
+    } else {
+        req_message.map(|req_message| quote!(, request: #req_message))
+    };
+    let send = send_method(method, config).then(|| quote!(+ Send));
+    let response = if method.server_streaming() {
+        let bound = lifetime.map(|lifetime| quote!(+ #lifetime));
+        quote!(impl ::prpc::Stream<Item = Result<#res_message, #error>> #send #bound)
+    } else {
+        res_message
+    };
+
+    match send {
+        Some(send) if !implementation => quote! {
+            fn #name #generics(#receiver #context #request)
+                -> impl ::core::future::Future<Output = Result<#response, #error>> #send
+        },
+        _ => quote! {
+            async fn #name #generics(#receiver #context #request) -> Result<#response, #error>
+        },
+    }
 }
 
 fn generate_supported_methods<T: Service>(service: &T, config: &Builder) -> TokenStream {
@@ -170,6 +731,63 @@ fn generate_supported_methods<T: Service>(service: &T, config: &Builder) -> Toke
     }
 }
 
+// Override `Service::method_timeout` with the `@timeout` annotations, if any.
+fn generate_method_timeouts<T: Service>(service: &T, config: &Builder) -> TokenStream {
+    let (paths, millis): (Vec<_>, Vec<_>) = service
+        .methods()
+        .iter()
+        .filter_map(|method| {
+            let millis = method_timeout_millis(method)?;
+            let path = crate::join_path(
+                config,
+                service.package(),
+                service.identifier(),
+                method.identifier(),
+            );
+            Some((Lit::Str(LitStr::new(&path, Span::call_site())), millis))
+        })
+        .unzip();
+    if paths.is_empty() {
+        return quote!();
+    }
+    quote! {
+        fn method_timeout(path: &str) -> Option<::core::time::Duration> {
+            match path {
+                #(#paths => Some(::core::time::Duration::from_millis(#millis)),)*
+                _ => None,
+            }
+        }
+    }
+}
+
+// Timeout of the `@timeout <duration>` annotation in the leading comment of a method, in
+// milliseconds. Durations are an integer with a unit of `ms`, `s`, `m` or `h`.
+fn method_timeout_millis<T: Method>(method: &T) -> Option<u64> {
+    method.comment().iter().find_map(|line| {
+        let parts: Vec<_> = line.as_ref().split_whitespace().collect();
+        match parts[..] {
+            ["@timeout", duration, ..] => Some(
+                parse_duration_millis(duration)
+                    .unwrap_or_else(|| panic!("Invalid @timeout duration {}", duration)),
+            ),
+            _ => None,
+        }
+    })
+}
+
+fn parse_duration_millis(duration: &str) -> Option<u64> {
+    let unit_start = duration.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = duration.split_at(unit_start);
+    let scale = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+    value.parse::<u64>().ok()?.checked_mul(scale)
+}
+
 fn generate_methods_enum<T: Service>(service: &T, config: &Builder) -> TokenStream {
     let mut paths = vec![];
     let mut variants = vec![];
@@ -209,10 +827,32 @@ fn generate_methods_enum<T: Service>(service: &T, config: &Builder) -> TokenStre
     }
 }
 
-fn generate_methods<T: Service>(service: &T, config: &Builder, json: bool) -> TokenStream {
+/// Which dispatch function of the generated server handles a method.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DispatchKind {
+    Unary,
+    ServerStreaming,
+    /// Client-streaming and bidirectional methods.
+    ClientStreaming,
+}
+
+impl DispatchKind {
+    fn of<T: Method>(method: &T) -> Self {
+        match (method.client_streaming(), method.server_streaming()) {
+            (false, false) => DispatchKind::Unary,
+            (false, true) => DispatchKind::ServerStreaming,
+            (true, _) => DispatchKind::ClientStreaming,
+        }
+    }
+}
+
+fn generate_methods<T: Service>(service: &T, config: &Builder, kind: DispatchKind) -> TokenStream {
     let mut stream = TokenStream::new();
 
     for method in service.methods() {
+        if DispatchKind::of(method) != kind {
+            continue;
+        }
         let path = crate::join_path(
             config,
             service.package(),
@@ -222,12 +862,7 @@ fn generate_methods<T: Service>(service: &T, config: &Builder, json: bool) -> To
         let method_path = Lit::Str(LitStr::new(&path, Span::call_site()));
         let method_ident = quote::format_ident!("{}", method.name());
 
-        let method_stream = match (method.client_streaming(), method.server_streaming()) {
-            (false, false) => generate_unary(method, config, method_ident, json),
-            _ => {
-                panic!("Streaming RPC not supported");
-            }
-        };
+        let method_stream = generate_method(method, config, &method_path, method_ident);
 
         let method = quote! {
             #method_path => {
@@ -240,43 +875,346 @@ fn generate_methods<T: Service>(service: &T, config: &Builder, json: bool) -> To
     stream
 }
 
-fn generate_unary<T: Method>(
+fn generate_method<T: Method>(
     method: &T,
     config: &Builder,
+    method_path: &Lit,
     method_ident: Ident,
-    json: bool,
 ) -> TokenStream {
     let (request, _response) =
         method.request_response_name(&config.proto_path, config.compile_well_known_types);
-
-    if json {
-        template_quote::quote! {
-            #(if request.is_none()) {
-                let response = self.inner.#method_ident().await?;
-            }
-            #(else) {
-                let data = _data.as_ref();
-                let input: #request = if data.is_empty() {
-                    Default::default()
-                } else if _query {
-                    ::prpc::serde_qs::from_bytes(data)?
-                } else {
-                    ::prpc::serde_json::from_slice(data)?
-                };
-                let response = self.inner.#method_ident(input).await?;
+    let policies = auth_policies(method);
+    let inner = match config.handler_receiver {
+        Receiver::Value => quote!(&self.inner),
+        Receiver::Ref | Receiver::Arc => quote!(&*self.inner),
+    };
+    let authorizer = generate_authorizer_trait(config);
+    let authorize = quote! {
+        #(
+            #authorizer::authorize(#inner, _context, #method_path, #policies)
+                .await
+                .map_err(::prpc::server::DispatchError::PermissionDenied)?;
+        )*
+    };
+    let limits = generate_limits(method);
+    let call = if method.client_streaming() {
+        generate_stream_call(request, method_ident, config, limits)
+    } else {
+        generate_call(request, method_ident, config, limits)
+    };
+    let encode = if config.scale_encoding {
+        quote! {
+            match _encoding {
+                ::prpc::server::Encoding::Scale => ::prpc::server::encode_scale(&response),
+                _ => _encoding.encode(&response)?,
             }
-            Ok(serde_json::to_vec(&response)?)
         }
     } else {
-        template_quote::quote! {
-            #(if request.is_none()) {
-                let response = self.inner.#method_ident().await?;
+        quote!(_encoding.encode(&response)?)
+    };
+
+    match (method.client_streaming(), method.server_streaming()) {
+        (false, false) => quote! {
+            #authorize
+            #call
+            Ok(#encode)
+        },
+        (true, false) => quote! {
+            #authorize
+            #call
+            Ok(::prpc::server::single_response(#encode))
+        },
+        (_, true) => {
+            let response = if typed_handler_error(config) {
+                quote!(::prpc::server::handler_error_stream(response))
+            } else {
+                quote!(response)
+            };
+            let encode_stream = if config.send_futures && !method.client_streaming() {
+                quote!(::prpc::server::encode_send_response_stream)
+            } else {
+                quote!(::prpc::server::encode_response_stream)
+            };
+            quote! {
+                #authorize
+                #call
+                Ok(#encode_stream(#response, move |response| Ok(#encode)))
             }
-            #(else) {
-                let input: #request = ::prpc::Message::decode(_data.as_ref())?;
-                let response = self.inner.#method_ident(input).await?;
+        }
+    }
+}
+
+// The server limits with the overrides of the `@limit <name> <value>` annotations in the leading
+// comment of a method.
+fn generate_limits<T: Method>(method: &T) -> TokenStream {
+    let overrides = method.comment().iter().filter_map(|line| {
+        let line = line.as_ref();
+        let parts: Vec<_> = line.split_whitespace().collect();
+        let invalid = |reason: &str| -> ! {
+            panic!(
+                "Invalid @limit annotation on {}: {}: {}",
+                method.name(),
+                line.trim(),
+                reason
+            )
+        };
+        let (name, value) = match parts[..] {
+            ["@limit", name, value, ..] => (name, value),
+            ["@limit", ..] => invalid("expected `@limit <name> <value>`"),
+            [annotation, ..] if annotation.starts_with("@limit") => {
+                invalid("expected `@limit <name> <value>`")
             }
-            Ok(::prpc::codec::encode_message_to_vec(&response))
+            _ => return None,
+        };
+        if !["max_body_size", "max_depth", "max_repeated"].contains(&name) {
+            invalid("unknown limit, expected max_body_size, max_depth or max_repeated");
+        }
+        let value: usize = value
+            .parse()
+            .unwrap_or_else(|_| invalid("the value is not an unsigned integer"));
+        let setter = quote::format_ident!("with_{}", name);
+        Some(quote!(.#setter(#value)))
+    });
+    quote!(self.limits #(#overrides)*)
+}
+
+// Decode a request message from the bytes `data` in `_encoding`, checking it against `limits`.
+fn generate_decode(config: &Builder, data: TokenStream) -> TokenStream {
+    let decode = if config.scale_encoding {
+        quote! {
+            match _encoding {
+                ::prpc::server::Encoding::Scale => ::prpc::server::decode_scale(data)?,
+                _ => _encoding.decode(data)?,
+            }
+        }
+    } else {
+        quote!(_encoding.decode(data)?)
+    };
+    quote! {{
+        let data: &[u8] = #data;
+        limits.check_size(data.len())?;
+        let input = #decode;
+        limits.check(&input)?;
+        input
+    }}
+}
+
+// Map the error of a handler to a `DispatchError`.
+fn generate_handler_error(config: &Builder) -> TokenStream {
+    if typed_handler_error(config) {
+        quote!(|err| ::prpc::server::DispatchError::Handler(::prpc::server::handler_error(err)))
+    } else {
+        quote!(::prpc::server::DispatchError::Handler)
+    }
+}
+
+// Decode the request from `_data` and call the handler, binding its output to `response`.
+fn generate_call(
+    request: Option<TokenStream>,
+    method_ident: Ident,
+    config: &Builder,
+    limits: TokenStream,
+) -> TokenStream {
+    let decode = generate_decode(config, quote!(_data.as_ref()));
+    let map_err = generate_handler_error(config);
+    let context = config.handler_context.then(|| quote!(_context,));
+    let (input, args) = match request {
+        Some(request) => (
+            quote! {
+                let limits = #limits;
+                let input: #request = #decode;
+            },
+            quote!(#context input),
+        ),
+        None => (quote!(), quote!(#context)),
+    };
+    quote! {
+        #input
+        let response = self
+            .inner
+            .#method_ident(#args)
+            .await
+            .map_err(#map_err)?;
+    }
+}
+
+// Decode the request frames from `_requests` and call the handler, binding its output to `response`.
+fn generate_stream_call(
+    request: Option<TokenStream>,
+    method_ident: Ident,
+    config: &Builder,
+    limits: TokenStream,
+) -> TokenStream {
+    let request = request.unwrap_or_else(|| quote!(()));
+    let context = config.handler_context.then(|| quote!(_context,));
+    let decode = generate_decode(config, quote!(&frame));
+    let decode = quote!(move |frame| Ok(#decode));
+    let map_err = generate_handler_error(config);
+    quote! {
+        let limits = #limits;
+        let input: ::prpc::server::RequestStream<'_, #request> =
+            ::prpc::server::decode_request_stream(_requests, #decode);
+        let response = self
+            .inner
+            .#method_ident(#context input)
+            .await
+            .map_err(#map_err)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestMethod {
+        name: &'static str,
+        comment: Vec<&'static str>,
+    }
+
+    impl Method for TestMethod {
+        type Comment = &'static str;
+
+        fn name(&self) -> &str {
+            self.name
+        }
+        fn identifier(&self) -> &str {
+            self.name
         }
+        fn client_streaming(&self) -> bool {
+            false
+        }
+        fn server_streaming(&self) -> bool {
+            false
+        }
+        fn comment(&self) -> &[Self::Comment] {
+            &self.comment
+        }
+        fn request_response_name(&self, _: &str, _: bool) -> (Option<TokenStream>, TokenStream) {
+            (Some(quote!(super::Request)), quote!(super::Response))
+        }
+    }
+
+    struct TestService(Vec<TestMethod>);
+
+    impl Service for TestService {
+        type Comment = &'static str;
+        type Method = TestMethod;
+
+        fn name(&self) -> &str {
+            "Echo"
+        }
+        fn package(&self) -> &str {
+            "echo"
+        }
+        fn identifier(&self) -> &str {
+            "Echo"
+        }
+        fn methods(&self) -> &[Self::Method] {
+            &self.0
+        }
+        fn comment(&self) -> &[Self::Comment] {
+            &[]
+        }
+    }
+
+    fn generate_service(methods: Vec<TestMethod>) -> String {
+        generate(&TestService(methods), &crate::configure())
+            .to_string()
+            .replace(' ', "")
+    }
+
+    #[test]
+    fn test_auth_annotation() {
+        let code = generate_service(vec![
+            TestMethod {
+                name: "admin",
+                comment: vec![" Admin only", " @auth admin"],
+            },
+            TestMethod {
+                name: "public",
+                comment: vec![],
+            },
+        ]);
+        let authorize = r#"::prpc::server::Authorizer::authorize(&self.inner,_context,"echo.Echo.admin","admin").await.map_err(::prpc::server::DispatchError::PermissionDenied)?;"#;
+        assert_eq!(code.matches(authorize).count(), 1);
+        // A denied call returns from the dispatch arm before reaching the handler.
+        let arm = code.find(r#""echo.Echo.admin"=>{"#).unwrap();
+        let check = code.find(authorize).unwrap();
+        let call = code.find(".inner.admin(").unwrap();
+        assert!(arm < check && check < call);
+        assert_eq!(
+            code.matches("::prpc::server::Authorizer::authorize(")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid @auth annotation on admin: @auth: missing policy")]
+    fn test_bare_auth_annotation() {
+        generate_service(vec![TestMethod {
+            name: "admin",
+            comment: vec![" @auth", " admin"],
+        }]);
+    }
+
+    #[test]
+    fn test_limit_annotation() {
+        let code = generate_service(vec![
+            TestMethod {
+                name: "upload",
+                comment: vec![" @limit max_body_size 1024", " @limit max_depth 4"],
+            },
+            TestMethod {
+                name: "get",
+                comment: vec![],
+            },
+        ]);
+        assert_eq!(
+            code.matches("self.limits.with_max_body_size(1024usize).with_max_depth(4usize)")
+                .count(),
+            1
+        );
+        assert!(code.contains("letlimits=self.limits;"));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Invalid @limit annotation on upload: @limit max_size 1024: unknown limit"
+    )]
+    fn test_unknown_limit_annotation() {
+        generate_service(vec![TestMethod {
+            name: "upload",
+            comment: vec![" @limit max_size 1024"],
+        }]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Invalid @limit annotation on upload: @limit max_depth many: the value is not"
+    )]
+    fn test_invalid_limit_value() {
+        generate_service(vec![TestMethod {
+            name: "upload",
+            comment: vec![" @limit max_depth many"],
+        }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid @limit annotation on upload: @limit max_depth: expected")]
+    fn test_missing_limit_value() {
+        generate_service(vec![TestMethod {
+            name: "upload",
+            comment: vec![" @limit max_depth"],
+        }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid @auth annotation on admin: @authorize admin: expected")]
+    fn test_invalid_auth_annotation() {
+        generate_service(vec![TestMethod {
+            name: "admin",
+            comment: vec![" @authorize admin"],
+        }]);
     }
 }
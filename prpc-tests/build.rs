@@ -0,0 +1,65 @@
+use prpc_build::Receiver;
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=proto");
+    compile("plain", "echo", prpc_build::configure());
+    compile(
+        "dyn_handlers",
+        "echo",
+        prpc_build::configure().dyn_handlers(true),
+    );
+    compile("send", "echo", prpc_build::configure().send_futures(true));
+    compile(
+        "no_service_name",
+        "echo",
+        prpc_build::configure().disable_service_name_emission(),
+    );
+    compile("guarded", "guarded", prpc_build::configure());
+    compile(
+        "context",
+        "counter",
+        prpc_build::configure().handler_context(true),
+    );
+    compile(
+        "by_ref",
+        "counter",
+        prpc_build::configure().handler_receiver(Receiver::Ref),
+    );
+    compile(
+        "by_arc",
+        "counter",
+        prpc_build::configure().handler_receiver(Receiver::Arc),
+    );
+    compile(
+        "typed_errors",
+        "counter",
+        prpc_build::configure().associated_error_type(true),
+    );
+    compile(
+        "error_type",
+        "counter",
+        prpc_build::configure().error_type("crate::CounterError"),
+    );
+    compile(
+        "scale",
+        "counter",
+        prpc_build::configure().enable_scale_encoding(),
+    );
+    compile(
+        "traced",
+        "counter",
+        prpc_build::configure().enable_tracing(),
+    );
+}
+
+// Compile `proto/{proto}.proto` with `builder` into `$OUT_DIR/{name}`.
+fn compile(name: &str, proto: &str, builder: prpc_build::Builder) {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join(name);
+    builder
+        .out_dir(out_dir)
+        .build_scale_ext(false)
+        .enable_serde_extension()
+        .compile(&[format!("proto/{proto}.proto")], &["proto"])
+        .unwrap();
+}
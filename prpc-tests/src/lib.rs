@@ -0,0 +1,453 @@
+//! Servers and clients generated by `prpc-build` from `proto/echo.proto`, one module per set of
+//! `Builder` options, from `proto/guarded.proto`, exercising the method annotations, and from
+//! `proto/counter.proto`, exercising the forms of the handler methods. A [`Loopback`] transport
+//! connects the generated clients to the generated servers in process. Exercised by the tests of
+//! this crate.
+#![allow(async_fn_in_trait)]
+
+extern crate alloc;
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use futures::{Stream, StreamExt};
+use prpc::client::{decode_response, encode_request, Error, RequestClient, ResponseStream};
+use prpc::codec::scale::{Decode, Encode};
+use prpc::server::{Code, Context, Encoding, ProtoError, RequestStream, Service};
+use prpc::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+
+macro_rules! include_generated {
+    ($package:literal: $($name:ident),*) => {$(
+        #[allow(clippy::all)]
+        pub mod $name {
+            include!(concat!(env!("OUT_DIR"), "/", stringify!($name), "/", $package, ".rs"));
+        }
+    )*};
+}
+
+include_generated!("echo.v1": plain, dyn_handlers, send, no_service_name);
+include_generated!("guarded.v1": guarded);
+include_generated!(
+    "counter.v1": context,
+    by_ref,
+    by_arc,
+    typed_errors,
+    error_type,
+    scale,
+    traced
+);
+
+/// A handler echoing the text of its requests, implementing the `EchoRpc` traits generated with
+/// the default receiver and error type.
+///
+/// `Say` fails with [`Code::InvalidArgument`] for an empty text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Echo;
+
+macro_rules! impl_echo {
+    ($($module:ident),*) => {$(
+        impl $module::echo_server::EchoRpc for Echo {
+            async fn say(self, request: $module::SayRequest) -> Result<$module::SayResponse, Error> {
+                if request.text.is_empty() {
+                    return Err(ProtoError::with_code(Code::InvalidArgument, "empty text").into());
+                }
+                Ok($module::SayResponse { text: request.text })
+            }
+
+            async fn repeat(
+                self,
+                request: $module::RepeatRequest,
+            ) -> Result<impl Stream<Item = Result<$module::SayResponse, Error>>, Error> {
+                let text = if request.shout {
+                    request.text.to_uppercase()
+                } else {
+                    request.text
+                };
+                let response = $module::SayResponse { text };
+                Ok(futures::stream::repeat(response)
+                    .take(request.count as usize)
+                    .map(Ok))
+            }
+
+            async fn collect(
+                self,
+                requests: RequestStream<'_, $module::SayRequest>,
+            ) -> Result<$module::SayResponse, Error> {
+                let texts = requests
+                    .map(|request| request.map(|request| request.text))
+                    .collect::<Vec<_>>()
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok($module::SayResponse {
+                    text: texts.join(" "),
+                })
+            }
+
+            async fn chat(
+                self,
+                requests: RequestStream<'_, $module::SayRequest>,
+            ) -> Result<impl Stream<Item = Result<$module::SayResponse, Error>>, Error> {
+                Ok(requests.map(|request| {
+                    request.map(|request| $module::SayResponse { text: request.text })
+                }))
+            }
+        }
+    )*};
+}
+
+impl_echo!(plain, dyn_handlers, send, no_service_name);
+
+/// A handler of the `Guarded` service responding with the blob of the request.
+///
+/// Authorizes the callers whose `role` metadata is the policy of the method.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Store;
+
+impl prpc::server::Authorizer for Store {
+    async fn authorize(&self, context: &Context, _path: &str, policy: &str) -> Result<(), Error> {
+        match context.metadata("role") {
+            Some(role) if role == policy => Ok(()),
+            Some(role) => Err(Error::msg(format!("role {} is not {}", role, policy))),
+            None => Err(ProtoError::with_code(Code::Unauthenticated, "missing role").into()),
+        }
+    }
+}
+
+impl guarded::guarded_server::GuardedRpc for Store {
+    async fn put(self, request: guarded::Blob) -> Result<guarded::Blob, Error> {
+        Ok(request)
+    }
+
+    async fn get(self, request: guarded::Blob) -> Result<guarded::Blob, Error> {
+        Ok(request)
+    }
+}
+
+/// A counter shared by the calls of its servers, implementing the `CounterRpc` traits.
+#[derive(Debug, Default)]
+pub struct Counter {
+    value: AtomicU64,
+}
+
+impl Counter {
+    /// Add `amount`, returning the new value.
+    pub fn add(&self, amount: u64) -> Result<u64, CounterError> {
+        if amount == 0 {
+            return Err(CounterError::ZeroAmount);
+        }
+        Ok(self.value.fetch_add(amount, Ordering::Relaxed) + amount)
+    }
+
+    /// Add one `amount` times, returning each new value.
+    pub fn tick(&self, amount: u64) -> Vec<u64> {
+        (0..amount)
+            .map(|_| self.value.fetch_add(1, Ordering::Relaxed) + 1)
+            .collect()
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// The domain error of a [`Counter`], sent to callers as [`Code::InvalidArgument`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterError {
+    ZeroAmount,
+}
+
+impl fmt::Display for CounterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CounterError::ZeroAmount => f.write_str("zero amount"),
+        }
+    }
+}
+
+impl std::error::Error for CounterError {}
+
+impl From<CounterError> for ProtoError {
+    fn from(error: CounterError) -> Self {
+        ProtoError::with_code(Code::InvalidArgument, error.to_string())
+    }
+}
+
+fn values<V>(values: Vec<u64>, value: fn(u64) -> V) -> impl Stream<Item = Result<V, Error>> {
+    futures::stream::iter(values.into_iter().map(move |v| Ok(value(v))))
+}
+
+/// Only counts the calls of a caller, failing with [`Code::Unauthenticated`] otherwise.
+impl context::counter_server::CounterRpc for Arc<Counter> {
+    async fn add(
+        self,
+        context: &Context,
+        request: context::AddRequest,
+    ) -> Result<context::Value, Error> {
+        caller(context)?;
+        let value = self
+            .as_ref()
+            .add(request.amount)
+            .map_err(ProtoError::from)?;
+        Ok(context::Value { value })
+    }
+
+    async fn tick(
+        self,
+        context: &Context,
+        request: context::AddRequest,
+    ) -> Result<impl Stream<Item = Result<context::Value, Error>>, Error> {
+        caller(context)?;
+        Ok(values(self.as_ref().tick(request.amount), |value| {
+            context::Value { value }
+        }))
+    }
+}
+
+impl scale::counter_server::CounterRpc for Arc<Counter> {
+    async fn add(self, request: scale::AddRequest) -> Result<scale::Value, Error> {
+        let value = self
+            .as_ref()
+            .add(request.amount)
+            .map_err(ProtoError::from)?;
+        Ok(scale::Value { value })
+    }
+
+    async fn tick(
+        self,
+        request: scale::AddRequest,
+    ) -> Result<impl Stream<Item = Result<scale::Value, Error>>, Error> {
+        Ok(values(self.as_ref().tick(request.amount), |value| {
+            scale::Value { value }
+        }))
+    }
+}
+
+impl traced::counter_server::CounterRpc for Arc<Counter> {
+    async fn add(self, request: traced::AddRequest) -> Result<traced::Value, Error> {
+        let value = self
+            .as_ref()
+            .add(request.amount)
+            .map_err(ProtoError::from)?;
+        Ok(traced::Value { value })
+    }
+
+    // Fails in place of the last value, exercising the errors of the response stream.
+    async fn tick(
+        self,
+        request: traced::AddRequest,
+    ) -> Result<impl Stream<Item = Result<traced::Value, Error>>, Error> {
+        let values = typed_tick(&self, request.amount, |value| traced::Value { value })
+            .map_err(ProtoError::from)?;
+        Ok(values.map(|value| Ok(value.map_err(ProtoError::from)?)))
+    }
+}
+
+impl by_ref::counter_server::CounterRpc for Counter {
+    async fn add(&self, request: by_ref::AddRequest) -> Result<by_ref::Value, Error> {
+        let value = self.add(request.amount).map_err(ProtoError::from)?;
+        Ok(by_ref::Value { value })
+    }
+
+    // The stream can not borrow the handler, so the values are counted upfront.
+    async fn tick<'a>(
+        &self,
+        request: by_ref::AddRequest,
+    ) -> Result<impl Stream<Item = Result<by_ref::Value, Error>> + 'a, Error> {
+        Ok(values(self.tick(request.amount), |value| by_ref::Value {
+            value,
+        }))
+    }
+}
+
+impl by_arc::counter_server::CounterRpc for Counter {
+    async fn add(self: Arc<Self>, request: by_arc::AddRequest) -> Result<by_arc::Value, Error> {
+        let value = Counter::add(&self, request.amount).map_err(ProtoError::from)?;
+        Ok(by_arc::Value { value })
+    }
+
+    // The stream owns the handler, so each value is counted as it is polled.
+    async fn tick(
+        self: Arc<Self>,
+        request: by_arc::AddRequest,
+    ) -> Result<impl Stream<Item = Result<by_arc::Value, Error>>, Error> {
+        Ok(futures::stream::iter(0..request.amount).map(move |_| {
+            let value = Counter::tick(&self, 1)[0];
+            Ok(by_arc::Value { value })
+        }))
+    }
+}
+
+impl typed_errors::counter_server::CounterRpc for Arc<Counter> {
+    type Error = CounterError;
+
+    async fn add(
+        self,
+        request: typed_errors::AddRequest,
+    ) -> Result<typed_errors::Value, Self::Error> {
+        let value = self.as_ref().add(request.amount)?;
+        Ok(typed_errors::Value { value })
+    }
+
+    async fn tick(
+        self,
+        request: typed_errors::AddRequest,
+    ) -> Result<impl Stream<Item = Result<typed_errors::Value, Self::Error>>, Self::Error> {
+        typed_tick(&self, request.amount, |value| typed_errors::Value { value })
+    }
+}
+
+impl error_type::counter_server::CounterRpc for Arc<Counter> {
+    async fn add(self, request: error_type::AddRequest) -> Result<error_type::Value, CounterError> {
+        let value = self.as_ref().add(request.amount)?;
+        Ok(error_type::Value { value })
+    }
+
+    async fn tick(
+        self,
+        request: error_type::AddRequest,
+    ) -> Result<impl Stream<Item = Result<error_type::Value, CounterError>>, CounterError> {
+        typed_tick(&self, request.amount, |value| error_type::Value { value })
+    }
+}
+
+// Fails for a zero amount, and otherwise in place of the last value, exercising the typed errors of
+// both the call and its response stream.
+fn typed_tick<V>(
+    counter: &Counter,
+    amount: u64,
+    value: fn(u64) -> V,
+) -> Result<impl Stream<Item = Result<V, CounterError>>, CounterError> {
+    if amount == 0 {
+        return Err(CounterError::ZeroAmount);
+    }
+    let values = counter
+        .tick(amount - 1)
+        .into_iter()
+        .map(move |v| Ok(value(v)));
+    Ok(futures::stream::iter(
+        values.chain([Err(CounterError::ZeroAmount)]),
+    ))
+}
+
+fn caller(context: &Context) -> Result<&str, ProtoError> {
+    context
+        .caller
+        .as_deref()
+        .ok_or_else(|| ProtoError::with_code(Code::Unauthenticated, "missing caller"))
+}
+
+/// A [`RequestClient`] dispatching the requests of a generated client to `service` in process,
+/// in `encoding` and with `context`, as a network transport would.
+///
+/// Errors of the dispatch reach the client as the [`ProtoError`] a transport would send.
+#[derive(Debug, Clone)]
+pub struct Loopback<S> {
+    pub service: S,
+    pub encoding: Encoding,
+    pub context: Context,
+}
+
+impl<S> Loopback<S> {
+    pub fn new(service: S) -> Self {
+        Self {
+            service,
+            encoding: Encoding::Protobuf,
+            context: Context::new(),
+        }
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn with_context(mut self, context: Context) -> Self {
+        self.context = context;
+        self
+    }
+}
+
+impl<S: Service + Clone> RequestClient for Loopback<S> {
+    async fn request<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+    where
+        T: Message + Serialize,
+        R: Message + DeserializeOwned + Default,
+    {
+        let body = encode_request(self.encoding, &body)?;
+        let response = self
+            .service
+            .clone()
+            .dispatch_request(&self.context, path, body, self.encoding)
+            .await
+            .map_err(ProtoError::from)?;
+        decode_response(self.encoding, &response)
+    }
+
+    async fn request_scale<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+    where
+        T: Encode,
+        R: Decode,
+    {
+        let response = self
+            .service
+            .clone()
+            .dispatch_request(&self.context, path, body.encode(), Encoding::Scale)
+            .await
+            .map_err(ProtoError::from)?;
+        R::decode(&mut &response[..]).map_err(Error::msg)
+    }
+
+    async fn request_stream<T, R>(
+        &self,
+        path: &str,
+        body: T,
+    ) -> Result<ResponseStream<'_, R>, Error>
+    where
+        T: Message + Serialize,
+        R: Message + DeserializeOwned + Default,
+    {
+        let body = encode_request(self.encoding, &body)?;
+        let responses = self
+            .service
+            .clone()
+            .dispatch_stream_request(&self.context, path, body, self.encoding)
+            .await
+            .map_err(ProtoError::from)?;
+        Ok(self.decode_responses(responses))
+    }
+
+    async fn request_client_stream<'a, T, R>(
+        &'a self,
+        path: &str,
+        requests: impl Stream<Item = T> + 'a,
+    ) -> Result<ResponseStream<'a, R>, Error>
+    where
+        T: Message + Serialize,
+        R: Message + DeserializeOwned + Default,
+    {
+        let encoding = self.encoding;
+        let requests = requests.map(move |request| encode_request(encoding, &request));
+        let responses = self
+            .service
+            .clone()
+            .dispatch_client_stream_request(&self.context, path, requests, encoding)
+            .await
+            .map_err(ProtoError::from)?;
+        Ok(self.decode_responses(responses))
+    }
+}
+
+impl<S> Loopback<S> {
+    fn decode_responses<'a, R>(
+        &self,
+        responses: prpc::server::ResponseStream<'a>,
+    ) -> ResponseStream<'a, R>
+    where
+        R: Message + DeserializeOwned + Default,
+    {
+        let encoding = self.encoding;
+        Box::pin(responses.map(move |response| decode_response(encoding, &response?)))
+    }
+}
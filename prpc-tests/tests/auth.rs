@@ -0,0 +1,41 @@
+use prpc::server::{Code, Context, DispatchError, Encoding};
+use prpc_tests::guarded::guarded_client::GuardedClient;
+use prpc_tests::guarded::guarded_server::GuardedServer;
+use prpc_tests::guarded::Blob;
+use prpc_tests::{Loopback, Store};
+
+const GET: &str = "guarded.v1.Guarded.Get";
+
+async fn get(context: Context, data: &[u8]) -> Result<Vec<u8>, DispatchError> {
+    GuardedServer::new(Store)
+        .dispatch_request(&context, GET, data, Encoding::Json)
+        .await
+}
+
+#[tokio::test]
+async fn test_auth_annotation() {
+    // `Get` is annotated with `@auth reader`.
+    let reader = Context::new().with_metadata("role", "reader");
+    assert!(get(reader.clone(), b"{}").await.is_ok());
+    let err = get(Context::new().with_metadata("role", "writer"), b"{}")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DispatchError::PermissionDenied(_)), "{}", err);
+    assert_eq!(err.code(), Code::PermissionDenied);
+    // Authorizers choose the code of their errors with a `ProtoError`.
+    let err = get(Context::new(), b"{}").await.unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+
+    // Callers are authorized before their request is decoded.
+    let err = get(Context::new(), b"{").await.unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    let err = get(reader.clone(), b"{").await.unwrap_err();
+    assert!(matches!(err, DispatchError::Decode(_)), "{}", err);
+
+    // Only the annotated methods are authorized.
+    let client = GuardedClient::new(Loopback::new(GuardedServer::new(Store)));
+    assert!(client.put(Blob::default()).await.is_ok());
+    assert!(client.get(Blob::default()).await.is_err());
+    let client = GuardedClient::new(Loopback::new(GuardedServer::new(Store)).with_context(reader));
+    assert!(client.get(Blob::default()).await.is_ok());
+}
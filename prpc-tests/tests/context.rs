@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use prpc::server::{Code, Context, ProtoError};
+use prpc_tests::context::counter_client::CounterClient;
+use prpc_tests::context::counter_server::CounterServer;
+use prpc_tests::context::AddRequest;
+use prpc_tests::{Counter, Loopback};
+
+type Client = CounterClient<Loopback<CounterServer<Arc<Counter>>>>;
+
+fn client(counter: &Arc<Counter>, context: Context) -> Client {
+    CounterClient::new(Loopback::new(CounterServer::new(counter.clone())).with_context(context))
+}
+
+#[tokio::test]
+async fn test_handler_context() {
+    let counter = Arc::new(Counter::default());
+    // The handlers receive the context of the transport.
+    let client = client(&counter, Context::new().with_caller("alice"));
+    let response = client.add(AddRequest { amount: 2 }).await.unwrap();
+    assert_eq!(response.value, 2);
+    let values = client
+        .tick(AddRequest { amount: 2 })
+        .await
+        .unwrap()
+        .map_ok(|response| response.value)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(values, [3, 4]);
+
+    let client = self::client(&counter, Context::new());
+    let err = client.add(AddRequest { amount: 1 }).await.unwrap_err();
+    let err = err.downcast::<ProtoError>().unwrap();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    assert!(client.tick(AddRequest { amount: 1 }).await.is_err());
+    assert_eq!(counter.value(), 4);
+}
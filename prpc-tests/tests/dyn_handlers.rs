@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use prpc::client::{BoxFuture, BoxStream, DynRequestClient, Error, ResponseStream};
+use prpc::server::{Code, Context, DynService, Encoding, ProtoError};
+use prpc_tests::dyn_handlers::echo_client::EchoClient;
+use prpc_tests::dyn_handlers::echo_server::{DynEchoRpc, EchoServer};
+use prpc_tests::dyn_handlers::{RepeatRequest, SayRequest};
+use prpc_tests::{Echo, Loopback};
+
+fn say(text: &str) -> SayRequest {
+    SayRequest { text: text.into() }
+}
+
+#[tokio::test]
+async fn test_dyn_handler() {
+    let handler: Box<dyn DynEchoRpc> = Box::new(Echo);
+    let response = EchoServer::new(handler)
+        .dispatch_request(
+            &Context::new(),
+            "echo.v1.Echo.Say",
+            br#"{"text":"hi"}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response, br#"{"text":"hi"}"#);
+
+    // Servers of shared handlers are `Clone`, as required by the `Loopback`.
+    let handler: Arc<dyn DynEchoRpc> = Arc::new(Echo);
+    let client = EchoClient::new(Loopback::new(EchoServer::new(handler)));
+    assert_eq!(client.say(say("hi")).await.unwrap().text, "hi");
+    let responses = client
+        .repeat(RepeatRequest {
+            text: "hi".into(),
+            count: 2,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(responses.len(), 2);
+    let requests = futures::stream::iter([say("a"), say("b")]);
+    assert_eq!(client.collect(requests).await.unwrap().text, "a b");
+}
+
+#[tokio::test]
+async fn test_dyn_service() {
+    let service: Arc<dyn DynService> = Arc::new(EchoServer::new(Echo));
+    assert_eq!(
+        (*service).dyn_methods(),
+        EchoServer::<Echo>::supported_methods()
+    );
+    let client = EchoClient::new(Loopback::new(service));
+    assert_eq!(client.say(say("hi")).await.unwrap().text, "hi");
+    let requests = futures::stream::iter([say("a"), say("b")]);
+    let responses = client
+        .chat(requests)
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(responses.len(), 2);
+}
+
+/// A transport exchanging the bodies in `encoding` with a [`DynService`], recording the request
+/// bodies it carries.
+struct RawTransport {
+    service: Arc<dyn DynService>,
+    encoding: Encoding,
+    context: Context,
+    bodies: Rc<RefCell<Vec<Vec<u8>>>>,
+}
+
+impl RawTransport {
+    fn new(encoding: Encoding) -> Self {
+        Self {
+            service: Arc::new(EchoServer::new(Echo)),
+            encoding,
+            context: Context::new(),
+            bodies: Rc::default(),
+        }
+    }
+}
+
+impl DynRequestClient for RawTransport {
+    fn dyn_encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    fn dyn_request<'a>(
+        &'a self,
+        path: &'a str,
+        body: Vec<u8>,
+        _timeout: Option<std::time::Duration>,
+    ) -> BoxFuture<'a, Result<Vec<u8>, Error>> {
+        Box::pin(async move {
+            self.bodies.borrow_mut().push(body.clone());
+            let response = self
+                .service
+                .dyn_dispatch_request(&self.context, path, &body, self.encoding)
+                .await
+                .map_err(ProtoError::from)?;
+            Ok(response)
+        })
+    }
+
+    fn dyn_request_stream<'a, 'b>(
+        &'a self,
+        path: &'b str,
+        body: Vec<u8>,
+        _timeout: Option<std::time::Duration>,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a, Vec<u8>>, Error>>
+    where
+        'a: 'b,
+    {
+        Box::pin(async move {
+            self.bodies.borrow_mut().push(body.clone());
+            let responses = self
+                .service
+                .dyn_dispatch_stream_request(&self.context, path, &body, self.encoding)
+                .await
+                .map_err(ProtoError::from)?;
+            Ok(responses)
+        })
+    }
+
+    fn dyn_request_client_stream<'a, 'b>(
+        &'a self,
+        path: &'b str,
+        requests: BoxStream<'a, Result<Vec<u8>, Error>>,
+        _timeout: Option<std::time::Duration>,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a, Vec<u8>>, Error>>
+    where
+        'a: 'b,
+    {
+        Box::pin(async move {
+            let responses = self
+                .service
+                .dyn_dispatch_client_stream_request(&self.context, path, requests, self.encoding)
+                .await
+                .map_err(ProtoError::from)?;
+            Ok(responses)
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_json_dyn_request_client() {
+    let transport = RawTransport::new(Encoding::Json);
+    let bodies = transport.bodies.clone();
+    let client = EchoClient::new(Box::new(transport) as Box<dyn DynRequestClient>);
+
+    assert_eq!(client.say(say("hi")).await.unwrap().text, "hi");
+    let responses = client
+        .repeat(RepeatRequest {
+            text: "hi".into(),
+            count: 2,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(responses.len(), 2);
+    let requests = futures::stream::iter([say("a"), say("b")]);
+    assert_eq!(client.collect(requests).await.unwrap().text, "a b");
+
+    // The messages reach the transport encoded as JSON.
+    assert_eq!(
+        *bodies.borrow(),
+        [
+            &br#"{"text":"hi"}"#[..],
+            br#"{"text":"hi","count":2,"shout":false}"#
+        ]
+    );
+
+    let err = client.say(say("")).await.unwrap_err();
+    let err = err.downcast::<ProtoError>().unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_protobuf_dyn_request_client() {
+    let transport = RawTransport::new(Encoding::Protobuf);
+    let bodies = transport.bodies.clone();
+    let client = EchoClient::new(Box::new(transport) as Box<dyn DynRequestClient>);
+    assert_eq!(client.say(say("hi")).await.unwrap().text, "hi");
+    assert_eq!(*bodies.borrow(), [prpc::Message::encode_to_vec(&say("hi"))]);
+}
@@ -0,0 +1,207 @@
+use futures::{StreamExt, TryStreamExt};
+use prpc::dynamic::prost_reflect::{DynamicMessage, MethodDescriptor};
+use prpc::dynamic::{DynamicHandler, DynamicService, MessageStream};
+use prpc::server::{Code, Context, DispatchError, Encoding, Error, MethodPaths, Service};
+use prpc::Message;
+use prpc_tests::plain::echo_client::EchoClient;
+use prpc_tests::plain::echo_server::EchoServer;
+use prpc_tests::plain::{RepeatRequest, SayRequest};
+use prpc_tests::{Echo, Loopback};
+
+const ECHO_DESCRIPTORS: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/plain/file_descriptor_set.bin"));
+const GUARDED_DESCRIPTORS: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/guarded/file_descriptor_set.bin"));
+
+/// A gateway forwarding the dynamic calls to the generated server in protobuf.
+#[derive(Clone)]
+struct Gateway;
+
+fn decode_output(method: &MethodDescriptor, frame: Vec<u8>) -> Result<DynamicMessage, Error> {
+    Ok(DynamicMessage::decode(method.output(), &frame[..])?)
+}
+
+impl DynamicHandler for Gateway {
+    async fn handle(
+        &self,
+        context: &Context,
+        method: MethodDescriptor,
+        request: DynamicMessage,
+    ) -> Result<DynamicMessage, Error> {
+        let path = format!("{}.{}", method.parent_service().full_name(), method.name());
+        let response = EchoServer::new(Echo)
+            .dispatch_request(context, &path, request.encode_to_vec(), Encoding::Protobuf)
+            .await?;
+        decode_output(&method, response)
+    }
+
+    async fn handle_stream<'a>(
+        &self,
+        context: &'a Context,
+        method: MethodDescriptor,
+        mut requests: MessageStream<'a>,
+    ) -> Result<MessageStream<'a>, Error> {
+        let path = format!("{}.{}", method.parent_service().full_name(), method.name());
+        let server = EchoServer::new(Echo);
+        let responses = if method.is_client_streaming() {
+            let requests = requests.map_ok(|request| request.encode_to_vec());
+            server
+                .dispatch_client_stream_request(context, &path, requests, Encoding::Protobuf)
+                .await?
+        } else {
+            let request = match requests.try_next().await? {
+                Some(request) => request,
+                None => DynamicMessage::new(method.input()),
+            };
+            server
+                .dispatch_stream_request(
+                    context,
+                    &path,
+                    request.encode_to_vec(),
+                    Encoding::Protobuf,
+                )
+                .await?
+        };
+        Ok(Box::pin(
+            responses.map(move |frame| decode_output(&method, frame?)),
+        ))
+    }
+}
+
+fn gateway() -> DynamicService<Gateway> {
+    DynamicService::new(ECHO_DESCRIPTORS, Gateway).unwrap()
+}
+
+#[tokio::test]
+async fn test_generated_client() {
+    // The generated client calls the dynamic service like the generated server.
+    let client = EchoClient::new(Loopback::new(gateway()));
+    let text = |text: &str| SayRequest { text: text.into() };
+    assert_eq!(client.say(text("hi")).await.unwrap().text, "hi");
+    let err = client.say(text("")).await.unwrap_err();
+    let err = err.downcast::<prpc::server::ProtoError>().unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    let responses = client
+        .repeat(RepeatRequest {
+            text: "hi".into(),
+            count: 2,
+            shout: true,
+        })
+        .await
+        .unwrap()
+        .map_ok(|response| response.text)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(responses, ["HI", "HI"]);
+
+    let requests = futures::stream::iter([text("a"), text("b")]);
+    assert_eq!(client.collect(requests).await.unwrap().text, "a b");
+    let requests = futures::stream::iter([text("a"), text("b")]);
+    let responses = client
+        .chat(requests)
+        .await
+        .unwrap()
+        .map_ok(|response| response.text)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(responses, ["a", "b"]);
+}
+
+#[tokio::test]
+async fn test_query() {
+    let context = Context::new();
+    // Numbers and bools of the query string decode to their field types.
+    let frames = gateway()
+        .dispatch_stream_request(
+            &context,
+            "echo.v1.Echo.Repeat",
+            "text=hi&count=2&shout=true",
+            Encoding::Query,
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(frames, [br#"{"text":"HI"}"#, br#"{"text":"HI"}"#]);
+
+    let err = gateway()
+        .dispatch_stream_request(
+            &context,
+            "echo.v1.Echo.Repeat",
+            "count=many",
+            Encoding::Query,
+        )
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    // Repeated fields, with indexed or unindexed items.
+    let service = DynamicService::new(
+        GUARDED_DESCRIPTORS,
+        |_method: MethodDescriptor, request: DynamicMessage| async move { Ok(request) },
+    )
+    .unwrap();
+    for query in ["tags[0]=a&tags[1]=b", "tags[]=a&tags[]=b"] {
+        let response = service
+            .clone()
+            .dispatch_request(&context, "guarded.v1.Guarded.Get", query, Encoding::Query)
+            .await
+            .unwrap();
+        assert_eq!(response, br#"{"data":"","tags":["a","b"]}"#, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn test_method_kinds() {
+    let context = Context::new();
+    // Methods are only dispatched as requests of their kind, as by the generated servers.
+    let err = gateway()
+        .dispatch_request(&context, "echo.v1.Echo.Repeat", b"{}", Encoding::Json)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DispatchError::MethodNotFound(_)), "{}", err);
+    let err = gateway()
+        .dispatch_stream_request(&context, "echo.v1.Echo.Chat", b"{}", Encoding::Json)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, DispatchError::MethodNotFound(_)), "{}", err);
+
+    // Handlers only handling unary calls reject streaming calls.
+    let service = DynamicService::new(
+        ECHO_DESCRIPTORS,
+        |_method: MethodDescriptor, request: DynamicMessage| async move { Ok(request) },
+    )
+    .unwrap();
+    let err = service
+        .dispatch_stream_request(&context, "echo.v1.Echo.Repeat", b"{}", Encoding::Json)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Code::Unimplemented);
+    assert!(err.to_string().contains("echo.v1.Echo.Repeat"), "{}", err);
+}
+
+#[test]
+fn test_method_paths() {
+    let mut paths: Vec<_> = gateway().method_paths().map(String::from).collect();
+    paths.sort();
+    let mut expected = EchoServer::<Echo>::supported_methods().to_vec();
+    expected.sort_unstable();
+    assert_eq!(paths, expected);
+
+    // The paths follow the path options of the generated servers.
+    let service = gateway().with_method_paths(&MethodPaths::new().disable_service_name_emission());
+    let mut paths: Vec<_> = service.method_paths().map(String::from).collect();
+    paths.sort();
+    let mut expected =
+        prpc_tests::no_service_name::echo_server::EchoServer::<Echo>::supported_methods().to_vec();
+    expected.sort_unstable();
+    assert_eq!(paths, expected);
+    assert!(paths.contains(&"echo.v1.Say".into()));
+}
@@ -0,0 +1,102 @@
+use futures::TryStreamExt;
+use prpc::server::{Code, Context, DispatchError, Encoding, ProtoError};
+use prpc_tests::plain::echo_client::EchoClient;
+use prpc_tests::plain::echo_server::EchoServer;
+use prpc_tests::plain::{RepeatRequest, SayRequest};
+use prpc_tests::{Echo, Loopback};
+
+const ENCODINGS: &[Encoding] = &[
+    Encoding::Protobuf,
+    Encoding::Json,
+    Encoding::Query,
+    Encoding::Cbor,
+    Encoding::MessagePack,
+];
+
+fn say(text: &str) -> SayRequest {
+    SayRequest { text: text.into() }
+}
+
+#[tokio::test]
+async fn test_client_encodings() {
+    for &encoding in ENCODINGS {
+        let client = EchoClient::new(Loopback::new(EchoServer::new(Echo)).with_encoding(encoding));
+        let response = client.say(say("hi")).await.unwrap();
+        assert_eq!(response.text, "hi", "{:?}", encoding);
+        let err = client.say(say("")).await.unwrap_err();
+        let err = err.downcast::<ProtoError>().unwrap();
+        assert_eq!(err.code(), Code::InvalidArgument, "{:?}", encoding);
+
+        let responses = client
+            .repeat(RepeatRequest {
+                text: "hi".into(),
+                count: 2,
+                shout: true,
+            })
+            .await
+            .unwrap()
+            .map_ok(|response| response.text)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(responses, ["HI", "HI"], "{:?}", encoding);
+        let requests = futures::stream::iter([say("a"), say("b")]);
+        let response = client.collect(requests).await.unwrap();
+        assert_eq!(response.text, "a b", "{:?}", encoding);
+    }
+}
+
+#[tokio::test]
+async fn test_decode_requests() {
+    // Query requests respond with JSON.
+    let response = EchoServer::new(Echo)
+        .dispatch_request(
+            &Context::new(),
+            "echo.v1.Echo.Say",
+            "text=hi",
+            Encoding::Query,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response, br#"{"text":"hi"}"#);
+
+    for encoding in [Encoding::Json, Encoding::Cbor, Encoding::MessagePack] {
+        let err = EchoServer::new(Echo)
+            .dispatch_request(&Context::new(), "echo.v1.Echo.Say", b"\xff", encoding)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, DispatchError::Decode(_)),
+            "{:?}: {}",
+            encoding,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_content_types() {
+    for &encoding in ENCODINGS {
+        let content_type = encoding.content_type();
+        let parsed = Encoding::from_content_type(content_type).unwrap();
+        // Query requests are sent as GET requests, and respond with JSON.
+        let expected = match encoding {
+            Encoding::Query => Encoding::Json,
+            encoding => encoding,
+        };
+        assert_eq!(parsed, expected);
+    }
+    assert_eq!(
+        Encoding::from_content_type("Application/JSON; charset=utf-8"),
+        Some(Encoding::Json)
+    );
+    assert_eq!(
+        Encoding::from_content_type("application/protobuf"),
+        Some(Encoding::Protobuf)
+    );
+    assert_eq!(
+        Encoding::from_content_type("application/x-msgpack"),
+        Some(Encoding::MessagePack)
+    );
+    assert_eq!(Encoding::from_content_type("text/plain"), None);
+}
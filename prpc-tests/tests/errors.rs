@@ -0,0 +1,49 @@
+use prpc::server::{Code, ComposedService, Context, DispatchError, Encoding, ProtoError, Service};
+use prpc_tests::plain::echo_server::EchoServer;
+use prpc_tests::Echo;
+
+async fn say(path: &str, data: &[u8]) -> Result<Vec<u8>, DispatchError> {
+    EchoServer::new(Echo)
+        .dispatch_request(&Context::new(), path, data, Encoding::Json)
+        .await
+}
+
+#[tokio::test]
+async fn test_dispatch_errors() {
+    // Each failure of a generated server is a variant of `DispatchError`, with its status code
+    // and HTTP status.
+    let err = say("echo.v1.Echo.Shout", b"{}").await.unwrap_err();
+    assert!(matches!(&err, DispatchError::MethodNotFound(path) if path == "echo.v1.Echo.Shout"));
+    assert_eq!((err.code(), err.http_status()), (Code::Unimplemented, 404));
+
+    let err = say("echo.v1.Echo.Say", b"{").await.unwrap_err();
+    assert!(matches!(err, DispatchError::Decode(_)), "{}", err);
+    assert_eq!(
+        (err.code(), err.http_status()),
+        (Code::InvalidArgument, 400)
+    );
+
+    let err = say("echo.v1.Echo.Say", b"{}").await.unwrap_err();
+    assert!(matches!(err, DispatchError::Handler(_)), "{}", err);
+    assert_eq!(
+        (err.code(), err.http_status()),
+        (Code::InvalidArgument, 400)
+    );
+    // The `ProtoError` of the handler reaches the caller as is.
+    let err = ProtoError::from(err);
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.message, "empty text");
+
+    // Streaming methods are not dispatched as unary requests.
+    let err = say("echo.v1.Echo.Repeat", b"{}").await.unwrap_err();
+    assert!(matches!(err, DispatchError::MethodNotFound(_)), "{}", err);
+
+    let err = ComposedService::<Echo, (EchoServer<Echo>,)>::new(Echo)
+        .dispatch_request(&Context::new(), "echo.v2.Echo.Say", b"{}", Encoding::Json)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DispatchError::ServiceNotFound(_)), "{}", err);
+    let err = ProtoError::from(err);
+    assert_eq!(err.code(), Code::Unimplemented);
+    assert_eq!(err.message, "Service not found: echo.v2.Echo.Say");
+}
@@ -0,0 +1,43 @@
+use prpc::server::{Context, DispatchError, Encoding};
+use prpc::Message;
+use prpc_tests::guarded::guarded_server::GuardedServer;
+use prpc_tests::guarded::Blob;
+use prpc_tests::Store;
+
+const PUT: &str = "guarded.v1.Guarded.Put";
+
+async fn put(blob: Blob) -> Result<Vec<u8>, DispatchError> {
+    GuardedServer::new(Store)
+        .dispatch_request(
+            &Context::new(),
+            PUT,
+            blob.encode_to_vec(),
+            Encoding::Protobuf,
+        )
+        .await
+}
+
+#[tokio::test]
+async fn test_limit_annotation() {
+    // `Put` is annotated with `@limit max_repeated 4`.
+    let tags = |count: usize| vec![String::from("tag"); count];
+    let blob = Blob {
+        data: vec![],
+        tags: tags(4),
+    };
+    assert_eq!(put(blob.clone()).await.unwrap(), blob.encode_to_vec());
+    let err = put(Blob {
+        data: vec![],
+        tags: tags(5),
+    })
+    .await
+    .unwrap_err();
+    assert!(matches!(err, DispatchError::LimitExceeded(_)), "{}", err);
+
+    // The bytes of a bytes field are not repeated elements.
+    let blob = Blob {
+        data: vec![0xab; 1000],
+        tags: tags(1),
+    };
+    assert_eq!(put(blob.clone()).await.unwrap(), blob.encode_to_vec());
+}
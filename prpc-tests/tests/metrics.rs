@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex};
+
+use futures::StreamExt;
+use prpc::server::{
+    CallMetrics, Code, Context, Encoding, Error, Metrics, MetricsMiddleware, MiddlewareService,
+    Service, UNKNOWN_METHOD,
+};
+use prpc_tests::plain::echo_server::EchoServer;
+use prpc_tests::Echo;
+
+/// The path, sizes and code of a recorded call.
+type Call = (String, usize, usize, Code);
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<Call>>>);
+
+impl Metrics for Recorder {
+    fn record(&self, call: &CallMetrics<'_>) {
+        assert_eq!(call.encoding, Encoding::Json);
+        let call = (
+            call.path.into(),
+            call.request_size,
+            call.response_size,
+            call.code,
+        );
+        self.0.lock().unwrap().push(call);
+    }
+}
+
+impl Recorder {
+    fn take(&self) -> Vec<Call> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+fn call(path: &str, request_size: usize, response_size: usize, code: Code) -> Call {
+    (path.into(), request_size, response_size, code)
+}
+
+#[tokio::test]
+async fn test_metrics() {
+    let recorder = Recorder::default();
+    let service = MiddlewareService::new(
+        MetricsMiddleware::new(recorder.clone()),
+        EchoServer::new(Echo),
+    );
+    let context = Context::new();
+    let unary = |path: &'static str, data: &'static [u8]| {
+        service
+            .clone()
+            .dispatch_request(&context, path, data, Encoding::Json)
+    };
+
+    unary("echo.v1.Echo.Say", br#"{"text":"hi"}"#)
+        .await
+        .unwrap();
+    unary("echo.v1.Echo.Say", b"{}").await.unwrap_err();
+    // Unknown paths share a label, so callers can not create a label per path.
+    unary("echo.v1.Echo.Shout", b"{}").await.unwrap_err();
+    assert_eq!(
+        recorder.take(),
+        [
+            call("echo.v1.Echo.Say", 13, 13, Code::Ok),
+            call("echo.v1.Echo.Say", 2, 0, Code::InvalidArgument),
+            call(UNKNOWN_METHOD, 2, 0, Code::Unimplemented),
+        ]
+    );
+
+    // Streaming calls are recorded at the end of their response stream.
+    let mut responses = service
+        .clone()
+        .dispatch_stream_request(
+            &context,
+            "echo.v1.Echo.Repeat",
+            br#"{"text":"hi","count":2}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+    while let Some(frame) = responses.next().await {
+        frame.unwrap();
+        assert!(recorder.take().is_empty());
+    }
+    assert_eq!(
+        recorder.take(),
+        [call("echo.v1.Echo.Repeat", 23, 26, Code::Ok)]
+    );
+
+    // Or when the stream is dropped before its end.
+    let mut responses = service
+        .clone()
+        .dispatch_stream_request(
+            &context,
+            "echo.v1.Echo.Repeat",
+            br#"{"text":"hi","count":2}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+    responses.next().await.unwrap().unwrap();
+    drop(responses);
+    assert_eq!(
+        recorder.take(),
+        [call("echo.v1.Echo.Repeat", 23, 13, Code::Cancelled)]
+    );
+
+    // The request size of client-streaming calls is the size of all frames.
+    let requests = futures::stream::iter([
+        Ok::<_, Error>(br#"{"text":"a"}"#.to_vec()),
+        Ok(br#"{"text":"b"}"#.to_vec()),
+    ]);
+    let responses = service
+        .dispatch_client_stream_request(&context, "echo.v1.Echo.Collect", requests, Encoding::Json)
+        .await
+        .unwrap();
+    assert_eq!(responses.count().await, 1);
+    assert_eq!(
+        recorder.take(),
+        [call("echo.v1.Echo.Collect", 24, 14, Code::Ok)]
+    );
+}
@@ -0,0 +1,163 @@
+use std::sync::{Arc, Mutex};
+
+use futures::TryStreamExt;
+use prpc::server::{
+    Code, Context, DispatchError, Encoding, Error, Middleware, MiddlewareService, ProtoError,
+    Request, ResponseStream, Service,
+};
+use prpc_tests::plain::echo_server::EchoServer;
+use prpc_tests::Echo;
+
+/// Rejects the requests without a caller before they reach the service.
+#[derive(Clone)]
+struct RequireCaller;
+
+impl RequireCaller {
+    fn check(context: &Context) -> Result<(), DispatchError> {
+        match context.caller {
+            Some(_) => Ok(()),
+            None => Err(DispatchError::Handler(
+                ProtoError::with_code(Code::Unauthenticated, "anonymous caller").into(),
+            )),
+        }
+    }
+}
+
+impl Middleware for RequireCaller {
+    async fn dispatch_request<S: Service>(
+        &self,
+        service: S,
+        context: &Context,
+        request: Request<'_>,
+    ) -> Result<Vec<u8>, DispatchError> {
+        Self::check(context)?;
+        request.dispatch(service, context).await
+    }
+
+    async fn dispatch_stream_request<'a, S: Service + 'a>(
+        &self,
+        service: S,
+        context: &'a Context,
+        request: Request<'_>,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        Self::check(context)?;
+        request.dispatch_stream(service, context).await
+    }
+}
+
+/// Records the paths of the unary requests it sees, tagged with its name, and their responses.
+#[derive(Clone, Default)]
+struct Log {
+    name: &'static str,
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl Middleware for Log {
+    async fn dispatch_request<S: Service>(
+        &self,
+        service: S,
+        context: &Context,
+        request: Request<'_>,
+    ) -> Result<Vec<u8>, DispatchError> {
+        let line = format!("{} {}", self.name, request.path);
+        self.lines.lock().unwrap().push(line);
+        let response = request.dispatch(service, context).await;
+        let line = match &response {
+            Ok(response) => format!("{} {}", self.name, String::from_utf8_lossy(response)),
+            Err(err) => format!("{} {:?}", self.name, err.code()),
+        };
+        self.lines.lock().unwrap().push(line);
+        response
+    }
+}
+
+#[tokio::test]
+async fn test_short_circuit() {
+    let service = MiddlewareService::new(RequireCaller, EchoServer::new(Echo));
+    let say = |context: Context| {
+        let service = service.clone();
+        async move {
+            service
+                .dispatch_request(
+                    &context,
+                    "echo.v1.Echo.Say",
+                    br#"{"text":"hi"}"#,
+                    Encoding::Json,
+                )
+                .await
+        }
+    };
+    let err = say(Context::new()).await.unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    assert_eq!(
+        say(Context::new().with_caller("alice")).await.unwrap(),
+        br#"{"text":"hi"}"#
+    );
+
+    let context = Context::new();
+    let err = service
+        .clone()
+        .dispatch_stream_request(
+            &context,
+            "echo.v1.Echo.Repeat",
+            br#"{"text":"hi","count":2}"#,
+            Encoding::Json,
+        )
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    // Client-streaming requests pass through by default.
+    let requests = futures::stream::iter([Ok::<_, Error>(br#"{"text":"a"}"#.to_vec())]);
+    let frames = service
+        .dispatch_client_stream_request(&context, "echo.v1.Echo.Collect", requests, Encoding::Json)
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(frames, [br#"{"text":"a"}"#]);
+}
+
+#[tokio::test]
+async fn test_nested() {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let log = |name| Log {
+        name,
+        lines: lines.clone(),
+    };
+    // The outermost middleware sees the requests first and the responses last.
+    let service = MiddlewareService::new(
+        log("outer"),
+        MiddlewareService::new(log("inner"), EchoServer::new(Echo)),
+    );
+    let response = service
+        .clone()
+        .dispatch_request(
+            &Context::new(),
+            "echo.v1.Echo.Say",
+            br#"{"text":"hi"}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response, br#"{"text":"hi"}"#);
+    let err = service
+        .dispatch_request(&Context::new(), "echo.v1.Echo.Say", b"{}", Encoding::Json)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(
+        *lines.lock().unwrap(),
+        [
+            "outer echo.v1.Echo.Say",
+            "inner echo.v1.Echo.Say",
+            r#"inner {"text":"hi"}"#,
+            r#"outer {"text":"hi"}"#,
+            "outer echo.v1.Echo.Say",
+            "inner echo.v1.Echo.Say",
+            "inner InvalidArgument",
+            "outer InvalidArgument",
+        ]
+    );
+}
@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use prpc::server::{Code, ProtoError};
+use prpc_tests::{by_arc, by_ref, Counter, Loopback};
+
+#[tokio::test]
+async fn test_ref_receiver() {
+    use by_ref::counter_client::CounterClient;
+    use by_ref::counter_server::CounterServer;
+    use by_ref::AddRequest;
+
+    // The clones of a server share its handler.
+    let server = CounterServer::new(Counter::default());
+    let client = CounterClient::new(Loopback::new(server.clone()));
+    assert_eq!(client.add(AddRequest { amount: 2 }).await.unwrap().value, 2);
+    let client = CounterClient::new(Loopback::new(server));
+    assert_eq!(client.add(AddRequest { amount: 3 }).await.unwrap().value, 5);
+    let values = client
+        .tick(AddRequest { amount: 2 })
+        .await
+        .unwrap()
+        .map_ok(|response| response.value)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(values, [6, 7]);
+
+    let err = client.add(AddRequest { amount: 0 }).await.unwrap_err();
+    let err = err.downcast::<ProtoError>().unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_arc_receiver() {
+    use by_arc::counter_client::CounterClient;
+    use by_arc::counter_server::CounterServer;
+    use by_arc::AddRequest;
+
+    // The server is built from the `Arc` of the app state, shared with the app.
+    let counter = Arc::new(Counter::default());
+    let client = CounterClient::new(Loopback::new(CounterServer::from(counter.clone())));
+    assert_eq!(client.add(AddRequest { amount: 2 }).await.unwrap().value, 2);
+    assert_eq!(counter.value(), 2);
+
+    // The response stream owns the handler and counts each value as it is polled.
+    let mut values = client.tick(AddRequest { amount: 2 }).await.unwrap();
+    assert_eq!(counter.value(), 2);
+    assert_eq!(values.next().await.unwrap().unwrap().value, 3);
+    assert_eq!(counter.value(), 3);
+    assert_eq!(values.next().await.unwrap().unwrap().value, 4);
+    assert!(values.next().await.is_none());
+}
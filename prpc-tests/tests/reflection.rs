@@ -0,0 +1,126 @@
+use prpc::reflection::{
+    Descriptors, GetFileDescriptorRequest, ListMethodsRequest, ReflectionService,
+};
+use prpc::server::{Code, ComposedService, Context, Encoding, MethodPaths, Service};
+use prpc::Message;
+use prpc_tests::no_service_name::echo_server::EchoServer;
+use prpc_tests::Echo;
+
+const ECHO_DESCRIPTORS: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/plain/file_descriptor_set.bin"));
+
+fn reflection() -> ReflectionService {
+    ReflectionService::new(Descriptors::new(ECHO_DESCRIPTORS).unwrap())
+}
+
+fn list_methods(service: &ReflectionService, name: &str) -> Vec<String> {
+    let request = ListMethodsRequest {
+        service: name.into(),
+    };
+    let mut paths: Vec<_> = service
+        .list_methods(request)
+        .unwrap()
+        .methods
+        .into_iter()
+        .map(|method| method.path)
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_list() {
+    let service = reflection();
+    assert_eq!(service.list_services().services, ["echo.v1.Echo"]);
+    assert_eq!(
+        list_methods(&service, "echo.v1.Echo"),
+        [
+            "echo.v1.Echo.Chat",
+            "echo.v1.Echo.Collect",
+            "echo.v1.Echo.Repeat",
+            "echo.v1.Echo.Say",
+        ]
+    );
+    let method = service
+        .list_methods(ListMethodsRequest {
+            service: "echo.v1.Echo".into(),
+        })
+        .unwrap()
+        .methods
+        .into_iter()
+        .find(|method| method.name == "Repeat")
+        .unwrap();
+    assert_eq!(method.input_type, "echo.v1.RepeatRequest");
+    assert_eq!(method.output_type, "echo.v1.SayResponse");
+    assert!(!method.client_streaming && method.server_streaming);
+
+    let err = service
+        .list_methods(ListMethodsRequest {
+            service: "echo.v2.Echo".into(),
+        })
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+}
+
+#[test]
+fn test_method_paths() {
+    // The reported paths follow the path options of the generated servers.
+    let descriptors = Descriptors::new(ECHO_DESCRIPTORS)
+        .unwrap()
+        .with_method_paths(&MethodPaths::new().disable_service_name_emission());
+    let paths = list_methods(&ReflectionService::new(descriptors), "echo.v1.Echo");
+    let mut expected = EchoServer::<Echo>::supported_methods().to_vec();
+    expected.sort_unstable();
+    assert_eq!(paths, expected);
+}
+
+#[test]
+fn test_get_file_descriptor() {
+    let service = reflection();
+    for symbol in ["echo.v1.Echo", "echo.v1.Echo.Say", ".echo.v1.RepeatRequest"] {
+        let response = service
+            .get_file_descriptor(GetFileDescriptorRequest {
+                symbol: symbol.into(),
+            })
+            .unwrap();
+        assert_eq!(response.file_descriptor_proto.len(), 1, "{}", symbol);
+        let file = prost_types::FileDescriptorProto::decode(&response.file_descriptor_proto[0][..])
+            .unwrap();
+        assert_eq!(file.package(), "echo.v1");
+    }
+    let err = service
+        .get_file_descriptor(GetFileDescriptorRequest {
+            symbol: "echo.v1.Missing".into(),
+        })
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+}
+
+#[derive(Clone)]
+struct App {
+    descriptors: Descriptors,
+}
+
+impl AsRef<Descriptors> for App {
+    fn as_ref(&self) -> &Descriptors {
+        &self.descriptors
+    }
+}
+
+#[tokio::test]
+async fn test_composed() {
+    let app = App {
+        descriptors: Descriptors::new(ECHO_DESCRIPTORS).unwrap(),
+    };
+    let service = ComposedService::<App, (ReflectionService<App>,)>::new(app);
+    let response = service
+        .dispatch_request(
+            &Context::new(),
+            "prpc.reflection.Reflection.ListServices",
+            b"",
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response, br#"{"services":["echo.v1.Echo"]}"#);
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use prpc::server::{
+    Context, DispatchError, DynService, Encoding, Middleware, MiddlewareService, Service,
+    ServiceRegistry,
+};
+use prpc_tests::guarded::guarded_server::GuardedServer;
+use prpc_tests::plain::echo_server::EchoServer;
+use prpc_tests::{Echo, Store};
+
+const PUT: &str = "guarded.v1.Guarded.Put";
+
+fn registry() -> ServiceRegistry {
+    let mut registry = ServiceRegistry::new();
+    registry
+        .register(|| EchoServer::new(Echo))
+        .register(|| GuardedServer::new(Store));
+    registry
+}
+
+#[tokio::test]
+async fn test_registry_dispatch() {
+    let registry = registry();
+    let mut services = registry.services();
+    services.sort_unstable();
+    assert_eq!(services, ["echo.v1.Echo", "guarded.v1.Guarded"]);
+    assert_eq!(registry.methods().len(), 6);
+
+    let context = Context::new();
+    let response = registry
+        .dispatch_request(
+            &context,
+            "echo.v1.Echo.Say",
+            br#"{"text":"hi"}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response, br#"{"text":"hi"}"#);
+    let frames = registry
+        .dispatch_stream_request(
+            &context,
+            "echo.v1.Echo.Repeat",
+            br#"{"text":"hi","count":2}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(frames.len(), 2);
+
+    let err = registry
+        .dispatch_request(&context, "echo.v1.Echo.Shout", b"{}", Encoding::Json)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DispatchError::MethodNotFound(_)), "{}", err);
+    let err = registry
+        .dispatch_request(&context, "echo.v2.Echo.Say", b"{}", Encoding::Json)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DispatchError::ServiceNotFound(_)), "{}", err);
+}
+
+#[tokio::test]
+async fn test_registry_replace() {
+    let mut registry = registry();
+    registry.register(|| EchoServer::new(Echo));
+    assert_eq!(registry.services().len(), 2);
+    assert_eq!(registry.methods().len(), 6);
+    // Replacing a service keeps the methods of the others.
+    let response = registry
+        .dispatch_request(&Context::new(), PUT, b"{}", Encoding::Json)
+        .await;
+    assert!(response.is_ok());
+}
+
+struct PassThrough;
+
+impl Middleware for PassThrough {}
+
+#[test]
+fn test_method_timeout() {
+    let timeout = Some(Duration::from_secs(2));
+    assert_eq!(GuardedServer::<Store>::method_timeout(PUT), timeout);
+    assert_eq!(GuardedServer::new(Store).timeout_for(PUT), timeout);
+    assert_eq!(
+        GuardedServer::<Store>::method_timeout("guarded.v1.Guarded.Get"),
+        None
+    );
+
+    let registry = Arc::new(registry());
+    assert_eq!(registry.method_timeout(PUT), timeout);
+    // Shared registries and services only know their methods at runtime, so the static methods
+    // of `Service` are empty, while `Service::timeout_for` reaches the registered services.
+    assert!(<Arc<ServiceRegistry> as Service>::methods().is_empty());
+    assert_eq!(<Arc<ServiceRegistry> as Service>::method_timeout(PUT), None);
+    assert_eq!(registry.timeout_for(PUT), timeout);
+    let service = MiddlewareService::new(PassThrough, registry);
+    assert_eq!(service.timeout_for(PUT), timeout);
+
+    let service: Arc<dyn DynService> = Arc::new(GuardedServer::new(Store));
+    assert_eq!(service.timeout_for(PUT), timeout);
+    assert_eq!(service.timeout_for("guarded.v1.Guarded.Get"), None);
+}
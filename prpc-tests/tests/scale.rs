@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use prpc::codec::scale::{Decode, Encode};
+use prpc::server::{Code, Context, DispatchError, Encoding, ProtoError};
+use prpc_tests::scale::counter_client::CounterClient;
+use prpc_tests::scale::counter_server::CounterServer;
+use prpc_tests::scale::{AddRequest, Value};
+use prpc_tests::{Counter, Loopback};
+
+fn server() -> CounterServer<Arc<Counter>> {
+    CounterServer::new(Arc::new(Counter::default()))
+}
+
+#[tokio::test]
+async fn test_scale_client() {
+    let client = CounterClient::new(Loopback::new(server()));
+    assert_eq!(
+        client
+            .add_scale(AddRequest { amount: 2 })
+            .await
+            .unwrap()
+            .value,
+        2
+    );
+    // The other encodings are still served.
+    assert_eq!(client.add(AddRequest { amount: 3 }).await.unwrap().value, 5);
+
+    let err = client
+        .add_scale(AddRequest { amount: 0 })
+        .await
+        .unwrap_err();
+    let err = err.downcast::<ProtoError>().unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_scale_dispatch() {
+    let context = Context::new();
+    let frames = server()
+        .dispatch_stream_request(
+            &context,
+            "counter.v1.Counter.Tick",
+            AddRequest { amount: 2 }.encode(),
+            Encoding::Scale,
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    let values: Vec<_> = frames
+        .iter()
+        .map(|frame| Value::decode(&mut &frame[..]).unwrap().value)
+        .collect();
+    assert_eq!(values, [1, 2]);
+
+    let err = server()
+        .dispatch_request(
+            &context,
+            "counter.v1.Counter.Add",
+            [1, 2, 3],
+            Encoding::Scale,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DispatchError::Decode(_)), "{}", err);
+
+    // Servers generated without SCALE reject it.
+    let err = prpc_tests::context::counter_server::CounterServer::new(Arc::new(Counter::default()))
+        .dispatch_request(
+            &context,
+            "counter.v1.Counter.Add",
+            AddRequest { amount: 1 }.encode(),
+            Encoding::Scale,
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, DispatchError::UnsupportedEncoding(Encoding::Scale)),
+        "{}",
+        err
+    );
+}
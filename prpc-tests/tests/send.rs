@@ -0,0 +1,88 @@
+use futures::TryStreamExt;
+use prpc::server::{Context, Encoding, SendService, Service};
+use prpc_tests::send::echo_server::EchoServer;
+use prpc_tests::Echo;
+
+#[tokio::test]
+async fn test_spawn_unary() {
+    let server = EchoServer::new(Echo);
+    let response = tokio::spawn(async move {
+        SendService::dispatch_request(
+            server,
+            &Context::new(),
+            "echo.v1.Echo.Say",
+            br#"{"text":"hi"}"#,
+            Encoding::Json,
+        )
+        .await
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(response, br#"{"text":"hi"}"#);
+}
+
+#[tokio::test]
+async fn test_spawn_server_streaming() {
+    let server = EchoServer::new(Echo);
+    let frames = tokio::spawn(async move {
+        let context = Context::new();
+        let responses = SendService::dispatch_stream_request(
+            server,
+            &context,
+            "echo.v1.Echo.Repeat",
+            br#"{"text":"hi","count":2}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+        responses.try_collect::<Vec<_>>().await
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(frames, [br#"{"text":"hi"}"#, br#"{"text":"hi"}"#]);
+}
+
+// Client-streaming and bidirectional requests are not `Send`, so they are dispatched on a local
+// task.
+#[tokio::test]
+async fn test_spawn_local_client_streaming() {
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            for (path, expected) in [
+                ("echo.v1.Echo.Collect", vec![br#"{"text":"a b"}"#.to_vec()]),
+                (
+                    "echo.v1.Echo.Chat",
+                    vec![br#"{"text":"a"}"#.to_vec(), br#"{"text":"b"}"#.to_vec()],
+                ),
+            ] {
+                let server = EchoServer::new(Echo);
+                let frames = tokio::task::spawn_local(async move {
+                    let context = Context::new();
+                    let requests = futures::stream::iter([
+                        Ok(br#"{"text":"a"}"#.to_vec()),
+                        Ok(br#"{"text":"b"}"#.to_vec()),
+                    ]);
+                    server
+                        .dispatch_client_stream_request(&context, path, requests, Encoding::Json)
+                        .await
+                        .unwrap()
+                        .try_collect::<Vec<_>>()
+                        .await
+                })
+                .await
+                .unwrap()
+                .unwrap();
+                assert_eq!(frames, expected);
+            }
+        })
+        .await;
+}
+
+#[test]
+fn test_send_server_is_service() {
+    fn assert_service<S: Service + SendService + Clone + Send + Sync + 'static>() {}
+    assert_service::<EchoServer<Echo>>();
+}
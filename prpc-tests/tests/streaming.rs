@@ -0,0 +1,157 @@
+use futures::TryStreamExt;
+use prpc::server::{Code, Context, Encoding, ProtoError};
+use prpc_tests::plain::echo_client::EchoClient;
+use prpc_tests::plain::echo_server::EchoServer;
+use prpc_tests::plain::{RepeatRequest, SayRequest};
+use prpc_tests::{Echo, Loopback};
+
+fn client() -> EchoClient<Loopback<EchoServer<Echo>>> {
+    EchoClient::new(Loopback::new(EchoServer::new(Echo)))
+}
+
+#[tokio::test]
+async fn test_unary() {
+    let response = client()
+        .say(SayRequest {
+            text: "hello".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.text, "hello");
+
+    let err = client().say(SayRequest::default()).await.unwrap_err();
+    let err = err.downcast::<ProtoError>().unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.message, "empty text");
+}
+
+#[tokio::test]
+async fn test_server_streaming() {
+    let client = client();
+    let responses = client
+        .repeat(RepeatRequest {
+            text: "hi".into(),
+            count: 3,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .map_ok(|response| response.text)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(responses, ["hi", "hi", "hi"]);
+
+    let responses = client
+        .repeat(RepeatRequest::default())
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert!(responses.is_empty());
+}
+
+#[tokio::test]
+async fn test_server_streaming_dispatch() {
+    let context = Context::new();
+    let server = EchoServer::new(Echo);
+
+    let frames = server
+        .clone()
+        .dispatch_stream_request(
+            &context,
+            "echo.v1.Echo.Repeat",
+            br#"{"text":"hi","count":2}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(frames, [br#"{"text":"hi"}"#, br#"{"text":"hi"}"#]);
+
+    // Server-streaming methods are not dispatched as unary requests, and the other way around.
+    let err = server
+        .clone()
+        .dispatch_request(&context, "echo.v1.Echo.Repeat", b"{}", Encoding::Json)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unimplemented);
+    let err = server
+        .dispatch_stream_request(&context, "echo.v1.Echo.Say", b"{}", Encoding::Json)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Code::Unimplemented);
+}
+
+fn say(text: &str) -> SayRequest {
+    SayRequest { text: text.into() }
+}
+
+#[tokio::test]
+async fn test_client_streaming() {
+    let client = client();
+    let requests = futures::stream::iter([say("a"), say("b"), say("c")]);
+    assert_eq!(client.collect(requests).await.unwrap().text, "a b c");
+    let requests = futures::stream::empty();
+    assert_eq!(client.collect(requests).await.unwrap().text, "");
+}
+
+#[tokio::test]
+async fn test_bidi_streaming() {
+    let client = client();
+    let requests = futures::stream::iter([say("a"), say("b")]);
+    let responses = client
+        .chat(requests)
+        .await
+        .unwrap()
+        .map_ok(|response| response.text)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(responses, ["a", "b"]);
+}
+
+#[tokio::test]
+async fn test_client_streaming_dispatch() {
+    let context = Context::new();
+    let server = EchoServer::new(Echo);
+
+    let requests = futures::stream::iter([
+        Ok(br#"{"text":"a"}"#.to_vec()),
+        Ok(br#"{"text":"b"}"#.to_vec()),
+    ]);
+    let frames = server
+        .clone()
+        .dispatch_client_stream_request(&context, "echo.v1.Echo.Collect", requests, Encoding::Json)
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(frames, [br#"{"text":"a b"}"#]);
+
+    // A request frame failing to decode fails the handler reading it.
+    let requests = futures::stream::iter([Ok(b"{".to_vec())]);
+    let err = server
+        .clone()
+        .dispatch_client_stream_request(&context, "echo.v1.Echo.Collect", requests, Encoding::Json)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    // The responses of a bidirectional method end with the requests.
+    let requests = futures::stream::iter([Ok(br#"{"text":"a"}"#.to_vec())]);
+    let frames = server
+        .dispatch_client_stream_request(&context, "echo.v1.Echo.Chat", requests, Encoding::Json)
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(frames, [br#"{"text":"a"}"#]);
+}
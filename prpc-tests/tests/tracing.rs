@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use futures::StreamExt;
+use prpc::server::Context;
+use prpc::trace::tracing::field::{Field, Visit};
+use prpc::trace::tracing::span::{Attributes, Id, Record};
+use prpc::trace::tracing::{subscriber, Event, Metadata, Subscriber};
+use prpc_tests::traced::counter_client::CounterClient;
+use prpc_tests::traced::counter_server::CounterServer;
+use prpc_tests::traced::AddRequest;
+use prpc_tests::{Counter, Loopback};
+
+type Fields = BTreeMap<&'static str, String>;
+
+#[derive(Debug)]
+struct Span {
+    name: &'static str,
+    fields: Fields,
+}
+
+/// A subscriber recording the spans and, with the name of their span, the events.
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<Span>>>,
+    events: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+    entered: Arc<Mutex<Vec<Id>>>,
+}
+
+struct Visitor<'a>(&'a mut Fields);
+
+impl Visit for Visitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+impl Recorder {
+    fn span(&self, name: &str) -> Fields {
+        let spans = self.spans.lock().unwrap();
+        let span = spans.iter().find(|span| span.name == name);
+        span.unwrap_or_else(|| panic!("no {} span in {:?}", name, spans))
+            .fields
+            .clone()
+    }
+
+    fn events(&self) -> Vec<(&'static str, Fields)> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn clear(&self) {
+        self.spans.lock().unwrap().clear();
+        self.events.lock().unwrap().clear();
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::new();
+        span.record(&mut Visitor(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(Span {
+            name: span.metadata().name(),
+            fields,
+        });
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut Visitor(
+            &mut spans[span.into_u64() as usize - 1].fields,
+        ));
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut Visitor(&mut fields));
+        let span = match self.entered.lock().unwrap().last() {
+            Some(id) => self.spans.lock().unwrap()[id.into_u64() as usize - 1].name,
+            None => "",
+        };
+        self.events.lock().unwrap().push((span, fields));
+    }
+
+    fn enter(&self, span: &Id) {
+        self.entered.lock().unwrap().push(span.clone());
+    }
+
+    fn exit(&self, _span: &Id) {
+        self.entered.lock().unwrap().pop();
+    }
+}
+
+#[tokio::test]
+async fn test_span_fields() {
+    let recorder = Recorder::default();
+    let _guard = subscriber::set_default(recorder.clone());
+    let server = CounterServer::new(Arc::new(Counter::default()));
+    let client = CounterClient::new(
+        Loopback::new(server).with_context(Context::new().with_request_id("42")),
+    );
+
+    assert_eq!(client.add(AddRequest { amount: 2 }).await.unwrap().value, 2);
+    let span = recorder.span("prpc.request");
+    assert_eq!(span["service"], "counter.v1.Counter");
+    assert_eq!(span["path"], "counter.v1.Counter.Add");
+    assert_eq!(span["request_size"], "2");
+    let span = recorder.span("prpc.dispatch");
+    assert_eq!(span["service"], "counter.v1.Counter");
+    assert_eq!(span["path"], "counter.v1.Counter.Add");
+    assert_eq!(span["request_id"], "42");
+    assert_eq!(span["encoding"], "Protobuf");
+    assert_eq!(span["request_size"], "2");
+    assert!(recorder.events().is_empty(), "{:?}", recorder.events());
+}
+
+#[tokio::test]
+async fn test_error_events() {
+    let recorder = Recorder::default();
+    let _guard = subscriber::set_default(recorder.clone());
+    let server = CounterServer::new(Arc::new(Counter::default()));
+    let client = CounterClient::new(Loopback::new(server));
+
+    assert!(client.add(AddRequest { amount: 0 }).await.is_err());
+    let events = recorder.events();
+    let (span, fields) = &events[0];
+    assert_eq!(*span, "prpc.dispatch");
+    assert_eq!(fields["message"], "handler failed");
+    assert_eq!(fields["code"], "InvalidArgument");
+    assert!(fields["error"].contains("zero amount"), "{:?}", fields);
+    let (span, fields) = &events[1];
+    assert_eq!(*span, "prpc.request");
+    assert_eq!(fields["message"], "request failed");
+    assert_eq!(events.len(), 2);
+
+    // The errors of the response streams are recorded in the span of their call.
+    recorder.clear();
+    let mut values = client.tick(AddRequest { amount: 2 }).await.unwrap();
+    assert!(values.next().await.unwrap().is_ok());
+    assert!(values.next().await.unwrap().is_err());
+    let events = recorder.events();
+    assert_eq!(events.len(), 1, "{:?}", events);
+    assert_eq!(events[0].0, "prpc.dispatch");
+    assert_eq!(events[0].1["message"], "response stream failed");
+    assert_eq!(
+        recorder.span("prpc.dispatch")["path"],
+        "counter.v1.Counter.Tick"
+    );
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use prpc::client::Error;
+use prpc::server::{Code, ProtoError};
+use prpc_tests::{error_type, typed_errors, Counter, Loopback};
+
+// The `ProtoError` converted from the `CounterError` of the handler.
+fn assert_zero_amount(err: Error) {
+    let err = err.downcast::<ProtoError>().unwrap();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.message, "zero amount");
+}
+
+macro_rules! test_typed_errors {
+    ($name:ident, $module:ident) => {
+        #[tokio::test]
+        async fn $name() {
+            use $module::counter_client::CounterClient;
+            use $module::counter_server::CounterServer;
+            use $module::AddRequest;
+
+            let server = CounterServer::new(Arc::new(Counter::default()));
+            let client = CounterClient::new(Loopback::new(server));
+            assert_eq!(client.add(AddRequest { amount: 2 }).await.unwrap().value, 2);
+            assert_zero_amount(client.add(AddRequest { amount: 0 }).await.unwrap_err());
+
+            assert_zero_amount(client.tick(AddRequest { amount: 0 }).await.err().unwrap());
+            let mut values = client.tick(AddRequest { amount: 2 }).await.unwrap();
+            assert_eq!(values.next().await.unwrap().unwrap().value, 3);
+            assert_zero_amount(values.next().await.unwrap().unwrap_err());
+        }
+    };
+}
+
+test_typed_errors!(test_associated_error_type, typed_errors);
+test_typed_errors!(test_error_type, error_type);
@@ -0,0 +1,487 @@
+use super::*;
+
+use alloc::sync::Arc;
+use core::time::Duration;
+use parity_scale_codec::DecodeAll;
+
+pub use crate::server::{BoxFuture, BoxStream, SendBoxFuture, SendBoxStream};
+
+/// Object safe form of [`RequestClient`], exchanging encoded messages.
+///
+/// Transports implement it on raw bytes: the request and response bodies are encoded in
+/// [`DynRequestClient::dyn_encoding`], or as SCALE for [`DynRequestClient::dyn_request_scale`],
+/// and pass through the transport untouched.
+///
+/// In turn, `Box<dyn DynRequestClient>` and `Arc<dyn DynRequestClient>` implement
+/// [`RequestClient`], encoding the requests and decoding the responses of the generated clients,
+/// so a generated client can use a transport chosen at runtime. The methods are prefixed with
+/// `dyn_` so that calls to the [`RequestClient`] methods of a transport implementing both traits
+/// stay unambiguous.
+pub trait DynRequestClient {
+    /// The encoding of the messages exchanged by the transport, see [`encode_request`] and
+    /// [`decode_response`].
+    fn dyn_encoding(&self) -> Encoding {
+        Encoding::Protobuf
+    }
+
+    /// Send an encoded request to a unary RPC and receive the encoded response.
+    fn dyn_request<'a>(
+        &'a self,
+        path: &'a str,
+        body: Vec<u8>,
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'a, Result<Vec<u8>, Error>>;
+
+    /// Like [`DynRequestClient::dyn_request`] with SCALE encoded messages, whatever the
+    /// [`DynRequestClient::dyn_encoding`], see [`RequestClient::request_scale`].
+    fn dyn_request_scale<'a>(
+        &'a self,
+        path: &'a str,
+        _body: Vec<u8>,
+        _timeout: Option<Duration>,
+    ) -> BoxFuture<'a, Result<Vec<u8>, Error>> {
+        Box::pin(
+            async move { anyhow::bail!("SCALE encoding not supported by this client: {path}") },
+        )
+    }
+
+    /// See [`RequestClient::request_stream`].
+    fn dyn_request_stream<'a, 'b>(
+        &'a self,
+        path: &'b str,
+        _body: Vec<u8>,
+        _timeout: Option<Duration>,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a, Vec<u8>>, Error>>
+    where
+        'a: 'b,
+    {
+        Box::pin(async move { anyhow::bail!("Streaming RPC not supported by this client: {path}") })
+    }
+
+    /// See [`RequestClient::request_client_stream`].
+    fn dyn_request_client_stream<'a, 'b>(
+        &'a self,
+        path: &'b str,
+        _requests: BoxStream<'a, Result<Vec<u8>, Error>>,
+        _timeout: Option<Duration>,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a, Vec<u8>>, Error>>
+    where
+        'a: 'b,
+    {
+        Box::pin(async move { anyhow::bail!("Streaming RPC not supported by this client: {path}") })
+    }
+}
+
+// Implement `RequestClient` for the pointers to a `DynRequestClient`.
+macro_rules! impl_request_client_for_dyn {
+    ($($pointer:ident),*) => {$(
+        impl<C: DynRequestClient + ?Sized> RequestClient for $pointer<C> {
+            async fn request<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned + Default,
+            {
+                dyn_request(&**self, path, body, None).await
+            }
+
+            async fn request_scale<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+            where
+                T: Encode,
+                R: Decode,
+            {
+                dyn_request_scale(&**self, path, body, None).await
+            }
+
+            async fn request_stream<T, R>(
+                &self,
+                path: &str,
+                body: T,
+            ) -> Result<ResponseStream<'_, R>, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned + Default,
+            {
+                dyn_request_stream(&**self, path, body, None).await
+            }
+
+            async fn request_client_stream<'a, T, R>(
+                &'a self,
+                path: &str,
+                requests: impl Stream<Item = T> + 'a,
+            ) -> Result<ResponseStream<'a, R>, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned + Default,
+            {
+                dyn_request_client_stream(&**self, path, requests, None).await
+            }
+
+            async fn request_with_timeout<T, R>(
+                &self,
+                path: &str,
+                body: T,
+                timeout: Duration,
+            ) -> Result<R, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned + Default,
+            {
+                dyn_request(&**self, path, body, Some(timeout)).await
+            }
+
+            async fn request_scale_with_timeout<T, R>(
+                &self,
+                path: &str,
+                body: T,
+                timeout: Duration,
+            ) -> Result<R, Error>
+            where
+                T: Encode,
+                R: Decode,
+            {
+                dyn_request_scale(&**self, path, body, Some(timeout)).await
+            }
+
+            async fn request_stream_with_timeout<T, R>(
+                &self,
+                path: &str,
+                body: T,
+                timeout: Duration,
+            ) -> Result<ResponseStream<'_, R>, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned + Default,
+            {
+                dyn_request_stream(&**self, path, body, Some(timeout)).await
+            }
+
+            async fn request_client_stream_with_timeout<'a, T, R>(
+                &'a self,
+                path: &str,
+                requests: impl Stream<Item = T> + 'a,
+                timeout: Duration,
+            ) -> Result<ResponseStream<'a, R>, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned + Default,
+            {
+                dyn_request_client_stream(&**self, path, requests, Some(timeout)).await
+            }
+        }
+    )*};
+}
+
+impl_request_client_for_dyn!(Box, Arc);
+
+async fn dyn_request<C, T, R>(
+    client: &C,
+    path: &str,
+    body: T,
+    timeout: Option<Duration>,
+) -> Result<R, Error>
+where
+    C: DynRequestClient + ?Sized,
+    T: Message + Serialize,
+    R: Message + DeserializeOwned + Default,
+{
+    let encoding = client.dyn_encoding();
+    let body = encode_request(encoding, &body)?;
+    let response = client.dyn_request(path, body, timeout).await?;
+    decode_response(encoding, &response)
+}
+
+async fn dyn_request_scale<C, T, R>(
+    client: &C,
+    path: &str,
+    body: T,
+    timeout: Option<Duration>,
+) -> Result<R, Error>
+where
+    C: DynRequestClient + ?Sized,
+    T: Encode,
+    R: Decode,
+{
+    let response = client
+        .dyn_request_scale(path, body.encode(), timeout)
+        .await?;
+    R::decode_all(&mut &response[..]).map_err(Error::msg)
+}
+
+async fn dyn_request_stream<'a, C, T, R>(
+    client: &'a C,
+    path: &str,
+    body: T,
+    timeout: Option<Duration>,
+) -> Result<ResponseStream<'a, R>, Error>
+where
+    C: DynRequestClient + ?Sized,
+    T: Message + Serialize,
+    R: Message + DeserializeOwned + Default,
+{
+    let encoding = client.dyn_encoding();
+    let body = encode_request(encoding, &body)?;
+    let responses = client.dyn_request_stream(path, body, timeout).await?;
+    Ok(decode_responses(encoding, responses))
+}
+
+async fn dyn_request_client_stream<'a, C, T, R>(
+    client: &'a C,
+    path: &str,
+    requests: impl Stream<Item = T> + 'a,
+    timeout: Option<Duration>,
+) -> Result<ResponseStream<'a, R>, Error>
+where
+    C: DynRequestClient + ?Sized,
+    T: Message + Serialize,
+    R: Message + DeserializeOwned + Default,
+{
+    let encoding = client.dyn_encoding();
+    let requests = Box::pin(requests.map(move |request| encode_request(encoding, &request)));
+    let responses = client
+        .dyn_request_client_stream(path, requests, timeout)
+        .await?;
+    Ok(decode_responses(encoding, responses))
+}
+
+fn decode_responses<R: Message + DeserializeOwned + Default>(
+    encoding: Encoding,
+    responses: ResponseStream<'_, Vec<u8>>,
+) -> ResponseStream<'_, R> {
+    Box::pin(responses.map(move |response| decode_response(encoding, &response?)))
+}
+
+/// `Send` form of [`DynRequestClient`], to use a [`SendRequestClient`] chosen at runtime.
+///
+/// `Box<dyn SendDynRequestClient>` and `Arc<dyn SendDynRequestClient>` implement
+/// [`SendRequestClient`], so they can drive the clients generated with `Builder::send_futures`
+/// of `prpc-build`.
+pub trait SendDynRequestClient: Send + Sync {
+    /// See [`DynRequestClient::dyn_encoding`].
+    fn dyn_send_encoding(&self) -> Encoding {
+        Encoding::Protobuf
+    }
+
+    /// See [`DynRequestClient::dyn_request`].
+    fn dyn_send_request<'a>(
+        &'a self,
+        path: &'a str,
+        body: Vec<u8>,
+        timeout: Option<Duration>,
+    ) -> SendBoxFuture<'a, Result<Vec<u8>, Error>>;
+
+    /// See [`DynRequestClient::dyn_request_scale`].
+    fn dyn_send_request_scale<'a>(
+        &'a self,
+        path: &'a str,
+        _body: Vec<u8>,
+        _timeout: Option<Duration>,
+    ) -> SendBoxFuture<'a, Result<Vec<u8>, Error>> {
+        Box::pin(
+            async move { anyhow::bail!("SCALE encoding not supported by this client: {path}") },
+        )
+    }
+
+    /// See [`DynRequestClient::dyn_request_stream`].
+    fn dyn_send_request_stream<'a, 'b>(
+        &'a self,
+        path: &'b str,
+        _body: Vec<u8>,
+        _timeout: Option<Duration>,
+    ) -> SendBoxFuture<'b, Result<SendResponseStream<'a, Vec<u8>>, Error>>
+    where
+        'a: 'b,
+    {
+        Box::pin(async move { anyhow::bail!("Streaming RPC not supported by this client: {path}") })
+    }
+
+    /// See [`DynRequestClient::dyn_request_client_stream`].
+    fn dyn_send_request_client_stream<'a, 'b>(
+        &'a self,
+        path: &'b str,
+        _requests: SendBoxStream<'a, Result<Vec<u8>, Error>>,
+        _timeout: Option<Duration>,
+    ) -> SendBoxFuture<'b, Result<SendResponseStream<'a, Vec<u8>>, Error>>
+    where
+        'a: 'b,
+    {
+        Box::pin(async move { anyhow::bail!("Streaming RPC not supported by this client: {path}") })
+    }
+}
+
+// Implement `SendRequestClient` for the pointers to a `SendDynRequestClient`.
+macro_rules! impl_send_request_client_for_dyn {
+    ($($pointer:ident),*) => {$(
+        impl<C: SendDynRequestClient + ?Sized> SendRequestClient for $pointer<C> {
+            async fn request<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+            where
+                T: Message + Serialize + Send,
+                R: Message + DeserializeOwned + Default,
+            {
+                send_dyn_request(&**self, path, body, None).await
+            }
+
+            async fn request_scale<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+            where
+                T: Encode + Send,
+                R: Decode,
+            {
+                send_dyn_request_scale(&**self, path, body, None).await
+            }
+
+            async fn request_stream<T, R>(
+                &self,
+                path: &str,
+                body: T,
+            ) -> Result<SendResponseStream<'_, R>, Error>
+            where
+                T: Message + Serialize + Send,
+                R: Message + DeserializeOwned + Default,
+            {
+                send_dyn_request_stream(&**self, path, body, None).await
+            }
+
+            async fn request_client_stream<'a, T, R>(
+                &'a self,
+                path: &str,
+                requests: impl Stream<Item = T> + Send + 'a,
+            ) -> Result<SendResponseStream<'a, R>, Error>
+            where
+                T: Message + Serialize + Send,
+                R: Message + DeserializeOwned + Default,
+            {
+                send_dyn_request_client_stream(&**self, path, requests, None).await
+            }
+
+            async fn request_with_timeout<T, R>(
+                &self,
+                path: &str,
+                body: T,
+                timeout: Duration,
+            ) -> Result<R, Error>
+            where
+                T: Message + Serialize + Send,
+                R: Message + DeserializeOwned + Default,
+            {
+                send_dyn_request(&**self, path, body, Some(timeout)).await
+            }
+
+            async fn request_scale_with_timeout<T, R>(
+                &self,
+                path: &str,
+                body: T,
+                timeout: Duration,
+            ) -> Result<R, Error>
+            where
+                T: Encode + Send,
+                R: Decode,
+            {
+                send_dyn_request_scale(&**self, path, body, Some(timeout)).await
+            }
+
+            async fn request_stream_with_timeout<T, R>(
+                &self,
+                path: &str,
+                body: T,
+                timeout: Duration,
+            ) -> Result<SendResponseStream<'_, R>, Error>
+            where
+                T: Message + Serialize + Send,
+                R: Message + DeserializeOwned + Default,
+            {
+                send_dyn_request_stream(&**self, path, body, Some(timeout)).await
+            }
+
+            async fn request_client_stream_with_timeout<'a, T, R>(
+                &'a self,
+                path: &str,
+                requests: impl Stream<Item = T> + Send + 'a,
+                timeout: Duration,
+            ) -> Result<SendResponseStream<'a, R>, Error>
+            where
+                T: Message + Serialize + Send,
+                R: Message + DeserializeOwned + Default,
+            {
+                send_dyn_request_client_stream(&**self, path, requests, Some(timeout)).await
+            }
+        }
+    )*};
+}
+
+impl_send_request_client_for_dyn!(Box, Arc);
+
+async fn send_dyn_request<C, T, R>(
+    client: &C,
+    path: &str,
+    body: T,
+    timeout: Option<Duration>,
+) -> Result<R, Error>
+where
+    C: SendDynRequestClient + ?Sized,
+    T: Message + Serialize,
+    R: Message + DeserializeOwned + Default,
+{
+    let encoding = client.dyn_send_encoding();
+    let body = encode_request(encoding, &body)?;
+    let response = client.dyn_send_request(path, body, timeout).await?;
+    decode_response(encoding, &response)
+}
+
+async fn send_dyn_request_scale<C, T, R>(
+    client: &C,
+    path: &str,
+    body: T,
+    timeout: Option<Duration>,
+) -> Result<R, Error>
+where
+    C: SendDynRequestClient + ?Sized,
+    T: Encode,
+    R: Decode,
+{
+    let response = client
+        .dyn_send_request_scale(path, body.encode(), timeout)
+        .await?;
+    R::decode_all(&mut &response[..]).map_err(Error::msg)
+}
+
+async fn send_dyn_request_stream<'a, C, T, R>(
+    client: &'a C,
+    path: &str,
+    body: T,
+    timeout: Option<Duration>,
+) -> Result<SendResponseStream<'a, R>, Error>
+where
+    C: SendDynRequestClient + ?Sized,
+    T: Message + Serialize,
+    R: Message + DeserializeOwned + Default,
+{
+    let encoding = client.dyn_send_encoding();
+    let body = encode_request(encoding, &body)?;
+    let responses = client.dyn_send_request_stream(path, body, timeout).await?;
+    Ok(send_decode_responses(encoding, responses))
+}
+
+async fn send_dyn_request_client_stream<'a, C, T, R>(
+    client: &'a C,
+    path: &str,
+    requests: impl Stream<Item = T> + Send + 'a,
+    timeout: Option<Duration>,
+) -> Result<SendResponseStream<'a, R>, Error>
+where
+    C: SendDynRequestClient + ?Sized,
+    T: Message + Serialize,
+    R: Message + DeserializeOwned + Default,
+{
+    let encoding = client.dyn_send_encoding();
+    let requests = Box::pin(requests.map(move |request| encode_request(encoding, &request)));
+    let responses = client
+        .dyn_send_request_client_stream(path, requests, timeout)
+        .await?;
+    Ok(send_decode_responses(encoding, responses))
+}
+
+fn send_decode_responses<R: Message + DeserializeOwned + Default>(
+    encoding: Encoding,
+    responses: SendResponseStream<'_, Vec<u8>>,
+) -> SendResponseStream<'_, R> {
+    Box::pin(responses.map(move |response| decode_response(encoding, &response?)))
+}
@@ -0,0 +1,398 @@
+//! Dispatch of RPCs described by a `FileDescriptorSet`, without generated code.
+//!
+//! [`DynamicService`] decodes requests into [`DynamicMessage`]s of the method input type, passes
+//! them to a [`DynamicHandler`] and encodes the returned messages. Protobuf, JSON and query string
+//! encodings are supported, converting with the descriptors. Query string values are converted to
+//! the type of their field, e.g. `?count=2&shout=true` sets a number and a bool.
+//!
+//! JSON uses the proto field names like the serde extension of `prpc-build`, but follows the
+//! protobuf JSON mapping otherwise, e.g. `bytes` fields are base64 rather than hex strings.
+
+use crate::server::{
+    decode_request_stream, encode_response_stream, Code, Context, DispatchError, Encoding, Error,
+    MethodPaths, ProtoError, ResponseStream, Service,
+};
+use crate::Message;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use futures_core::Stream;
+use prost_reflect::{
+    DescriptorError, DescriptorPool, DeserializeOptions, DynamicMessage, FieldDescriptor, Kind,
+    MessageDescriptor, MethodDescriptor, ReflectMessage, SerializeOptions,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Stream of the request or response messages of a streaming call of a [`DynamicHandler`].
+pub type MessageStream<'a> = Pin<Box<dyn Stream<Item = Result<DynamicMessage, Error>> + 'a>>;
+
+pub use prost_reflect;
+
+/// Handles the calls dispatched by a [`DynamicService`].
+///
+/// Implemented for closures taking the method and the request message, which only handle unary
+/// calls.
+pub trait DynamicHandler {
+    /// Handle a call of the unary `method`. The response must be a message of the method output
+    /// type.
+    async fn handle(
+        &self,
+        context: &Context,
+        method: MethodDescriptor,
+        request: DynamicMessage,
+    ) -> Result<DynamicMessage, Error>;
+
+    /// Handle a call of the streaming `method`, responding with messages of the method output
+    /// type. Server-streaming methods receive their single request message in `requests`, and
+    /// client-streaming methods must respond with a single message.
+    ///
+    /// Fails with [`Code::Unimplemented`] unless overridden.
+    async fn handle_stream<'a>(
+        &self,
+        _context: &'a Context,
+        method: MethodDescriptor,
+        _requests: MessageStream<'a>,
+    ) -> Result<MessageStream<'a>, Error> {
+        Err(ProtoError::with_code(
+            Code::Unimplemented,
+            alloc::format!("Streaming method {} is not handled", method.full_name()),
+        )
+        .into())
+    }
+}
+
+impl<F, Fut> DynamicHandler for F
+where
+    F: Fn(MethodDescriptor, DynamicMessage) -> Fut,
+    Fut: Future<Output = Result<DynamicMessage, Error>>,
+{
+    async fn handle(
+        &self,
+        _context: &Context,
+        method: MethodDescriptor,
+        request: DynamicMessage,
+    ) -> Result<DynamicMessage, Error> {
+        self(method, request).await
+    }
+}
+
+/// A [`Service`] dispatching the methods of a descriptor pool to a [`DynamicHandler`].
+///
+/// Methods are routed by `{package}.{Service}.{Method}`, the default path of `prpc-build`, or as
+/// configured with [`DynamicService::with_method_paths`]. As for the generated servers,
+/// streaming methods are only dispatched as streaming requests of their kind. As the methods are
+/// only known at runtime, [`Service::methods`] is empty, see [`DynamicService::method_paths`]
+/// instead.
+pub struct DynamicService<H> {
+    methods: Arc<HashMap<String, MethodDescriptor>>,
+    handler: H,
+}
+
+impl<H: Clone> Clone for DynamicService<H> {
+    fn clone(&self) -> Self {
+        Self {
+            methods: self.methods.clone(),
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+impl<H: DynamicHandler> DynamicService<H> {
+    /// Serve the services of an encoded `FileDescriptorSet`, e.g. the `file_descriptor_set.bin`
+    /// written by `prpc-build`.
+    pub fn new(file_descriptor_set: &[u8], handler: H) -> Result<Self, DescriptorError> {
+        Ok(Self::from_pool(
+            &DescriptorPool::decode(file_descriptor_set)?,
+            handler,
+        ))
+    }
+
+    /// Serve the services of `pool`.
+    pub fn from_pool(pool: &DescriptorPool, handler: H) -> Self {
+        let paths = MethodPaths::default();
+        let methods = pool
+            .services()
+            .flat_map(|service| service.methods().collect::<Vec<_>>())
+            .map(|method| (method_path(&paths, &method), method))
+            .collect();
+        Self {
+            methods: Arc::new(methods),
+            handler,
+        }
+    }
+
+    /// Route the methods by the paths of `paths`, matching servers generated with the same path
+    /// options of `prpc-build`.
+    pub fn with_method_paths(mut self, paths: &MethodPaths) -> Self {
+        let methods = self
+            .methods
+            .values()
+            .map(|method| (method_path(paths, method), method.clone()))
+            .collect();
+        self.methods = Arc::new(methods);
+        self
+    }
+
+    /// The paths of the served methods.
+    pub fn method_paths(&self) -> impl Iterator<Item = &str> {
+        self.methods.keys().map(String::as_str)
+    }
+}
+
+impl<H> DynamicService<H> {
+    /// The method at `path`, if it is of the kind accepted by `kind`.
+    fn method(
+        &self,
+        path: &str,
+        kind: impl Fn(&MethodDescriptor) -> bool,
+    ) -> Result<MethodDescriptor, DispatchError> {
+        self.methods
+            .get(path)
+            .filter(|method| kind(method))
+            .cloned()
+            .ok_or_else(|| DispatchError::MethodNotFound(path.into()))
+    }
+}
+
+fn method_path(paths: &MethodPaths, method: &MethodDescriptor) -> String {
+    let service = method.parent_service();
+    paths.join(service.package_name(), service.name(), method.name())
+}
+
+impl<H: DynamicHandler> Service for DynamicService<H> {
+    type Methods = &'static [&'static str];
+
+    fn methods() -> Self::Methods {
+        &[]
+    }
+
+    async fn dispatch_request(
+        self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        let method = self.method(path, |method| {
+            !method.is_client_streaming() && !method.is_server_streaming()
+        })?;
+        let output = method.output();
+        let request = decode(method.input(), data.as_ref(), encoding)?;
+        let response = self
+            .handler
+            .handle(context, method, request)
+            .await
+            .map_err(DispatchError::Handler)?;
+        encode_response(&output, &response, encoding).map_err(DispatchError::Encode)
+    }
+
+    async fn dispatch_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        let method = self.method(path, |method| {
+            !method.is_client_streaming() && method.is_server_streaming()
+        })?;
+        let request = decode(method.input(), data.as_ref(), encoding)?;
+        let requests = Box::pin(futures_util::stream::once(async move { Ok(request) }));
+        self.dispatch_stream(context, method, requests, encoding)
+            .await
+    }
+
+    async fn dispatch_client_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        let method = self.method(path, MethodDescriptor::is_client_streaming)?;
+        let input = method.input();
+        let requests = decode_request_stream(requests, move |frame| {
+            decode(input.clone(), &frame, encoding).map_err(Error::new)
+        });
+        self.dispatch_stream(context, method, requests, encoding)
+            .await
+    }
+}
+
+impl<H: DynamicHandler> DynamicService<H> {
+    async fn dispatch_stream<'a>(
+        &self,
+        context: &'a Context,
+        method: MethodDescriptor,
+        requests: MessageStream<'a>,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        let output = method.output();
+        let responses = self
+            .handler
+            .handle_stream(context, method, requests)
+            .await
+            .map_err(DispatchError::Handler)?;
+        Ok(encode_response_stream(responses, move |response| {
+            encode_response(&output, &response, encoding)
+        }))
+    }
+}
+
+fn decode(
+    desc: MessageDescriptor,
+    data: &[u8],
+    encoding: Encoding,
+) -> Result<DynamicMessage, DispatchError> {
+    let options = DeserializeOptions::new().deny_unknown_fields(false);
+    match encoding {
+        Encoding::Protobuf => {
+            DynamicMessage::decode(desc, data).map_err(|err| DispatchError::Decode(err.into()))
+        }
+        _ if data.is_empty() && encoding.is_json() => Ok(DynamicMessage::new(desc)),
+        Encoding::Json => {
+            let mut deserializer = serde_json::Deserializer::from_slice(data);
+            DynamicMessage::deserialize_with_options(desc, &mut deserializer, &options)
+                .and_then(|message| deserializer.end().map(|()| message))
+                .map_err(|err| DispatchError::Decode(err.into()))
+        }
+        Encoding::Query => {
+            let fields: serde_json::Map<String, Value> =
+                serde_qs::from_bytes(data).map_err(|err| DispatchError::Decode(err.into()))?;
+            let fields = typed_query_fields(&desc, fields);
+            DynamicMessage::deserialize_with_options(desc, Value::from(fields), &options)
+                .map_err(|err| DispatchError::Decode(err.into()))
+        }
+        _ => Err(DispatchError::UnsupportedEncoding(encoding)),
+    }
+}
+
+// Convert the string values of a decoded query string to the JSON types of the fields of `desc`,
+// e.g. numbers and bools. Values failing to convert are left as strings to fail decoding.
+fn typed_query_fields(
+    desc: &MessageDescriptor,
+    fields: serde_json::Map<String, Value>,
+) -> serde_json::Map<String, Value> {
+    fields
+        .into_iter()
+        .map(|(name, value)| {
+            let field = desc
+                .get_field_by_name(&name)
+                .or_else(|| desc.get_field_by_json_name(&name));
+            let value = match field {
+                Some(field) => typed_query_field(&field, value),
+                None => value,
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+fn typed_query_field(field: &FieldDescriptor, value: Value) -> Value {
+    if field.is_map() {
+        let Kind::Message(entry) = field.kind() else {
+            return value;
+        };
+        let value_field = entry.map_entry_value_field();
+        return match value {
+            Value::Object(entries) => entries
+                .into_iter()
+                .map(|(key, value)| (key, typed_query_value(&value_field.kind(), value)))
+                .collect(),
+            value => value,
+        };
+    }
+    if field.is_list() {
+        return match value {
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| typed_query_value(&field.kind(), item))
+                .collect(),
+            // Indexed items, e.g. `tags[0]=a&tags[1]=b`, decode as an object.
+            Value::Object(items) => {
+                let mut items: Vec<_> = items.into_iter().collect();
+                items.sort_by_key(|(index, _)| index.parse::<usize>().unwrap_or(usize::MAX));
+                items
+                    .into_iter()
+                    .map(|(_, item)| typed_query_value(&field.kind(), item))
+                    .collect()
+            }
+            value => Value::Array(alloc::vec![typed_query_value(&field.kind(), value)]),
+        };
+    }
+    typed_query_value(&field.kind(), value)
+}
+
+fn typed_query_value(kind: &Kind, value: Value) -> Value {
+    let text = match value {
+        Value::String(text) => text,
+        Value::Object(fields) => {
+            return match kind {
+                Kind::Message(desc) => Value::Object(typed_query_fields(desc, fields)),
+                _ => Value::Object(fields),
+            };
+        }
+        value => return value,
+    };
+    let typed = match kind {
+        Kind::Bool => text.parse::<bool>().ok().map(Value::from),
+        Kind::Int32
+        | Kind::Sint32
+        | Kind::Sfixed32
+        | Kind::Int64
+        | Kind::Sint64
+        | Kind::Sfixed64 => text.parse::<i64>().ok().map(Value::from),
+        Kind::Uint32 | Kind::Fixed32 | Kind::Uint64 | Kind::Fixed64 => {
+            text.parse::<u64>().ok().map(Value::from)
+        }
+        Kind::Float | Kind::Double => text
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .map(Value::from),
+        _ => None,
+    };
+    typed.unwrap_or(Value::String(text))
+}
+
+// Encode a response of a method with the `output` type.
+fn encode_response(
+    output: &MessageDescriptor,
+    response: &DynamicMessage,
+    encoding: Encoding,
+) -> Result<Vec<u8>, Error> {
+    if response.descriptor() != *output {
+        return Err(anyhow::anyhow!(
+            "Expected a {} response, got {}",
+            output.full_name(),
+            response.descriptor().full_name()
+        ));
+    }
+    encode(response, encoding)
+}
+
+fn encode(message: &DynamicMessage, encoding: Encoding) -> Result<Vec<u8>, Error> {
+    let options = SerializeOptions::new()
+        .use_proto_field_name(true)
+        .skip_default_fields(false);
+    match encoding {
+        Encoding::Protobuf => Ok(message.encode_to_vec()),
+        Encoding::Json | Encoding::Query => {
+            let mut serializer = serde_json::Serializer::new(Vec::new());
+            message.serialize_with_options(&mut serializer, &options)?;
+            Ok(serializer.into_inner())
+        }
+        // Requests in other encodings fail to decode first.
+        _ => Err(DispatchError::UnsupportedEncoding(encoding).into()),
+    }
+}
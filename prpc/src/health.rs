@@ -0,0 +1,417 @@
+//! Health checking service following the semantics of the gRPC health protocol.
+//!
+//! The application keeps a [`HealthReporter`] to update the status of its services at runtime,
+//! and exposes it to [`HealthService`] through `AsRef`:
+//!
+//! ```ignore
+//! impl AsRef<HealthReporter> for App {
+//!     fn as_ref(&self) -> &HealthReporter {
+//!         &self.health
+//!     }
+//! }
+//!
+//! type Api = ComposedService<App, (EchoServer<App>, HealthService<App>)>;
+//!
+//! app.health.set_status(<EchoServer<App> as NamedService>::NAME, ServingStatus::NotServing);
+//! ```
+//!
+//! Both `Check` and the server-streaming `Watch` methods are served. `Watch` responds with the
+//! current status, then with each change of it, and never ends on its own.
+
+use crate::server::{
+    encode_response_stream, encode_send_response_stream, Code, Context, DispatchError, Encoding,
+    NamedService, ProtoError, ResponseStream, SendResponseStream, SendService, Service,
+};
+use crate::Message;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::task::{Poll, Waker};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Serving status of a service, `grpc.health.v1.HealthCheckResponse.ServingStatus`.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[repr(i32)]
+pub enum ServingStatus {
+    Unknown = 0,
+    Serving = 1,
+    NotServing = 2,
+    ServiceUnknown = 3,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct HealthCheckRequest {
+    /// The service to check, or the empty string for the overall health of the server.
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub service: String,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct HealthCheckResponse {
+    #[prost(enumeration = "ServingStatus", tag = "1")]
+    #[serde(default, with = "status_name")]
+    pub status: i32,
+}
+
+/// Serialize the status by name, like the protobuf JSON mapping of enums.
+mod status_name {
+    use super::ServingStatus;
+    use core::convert::TryFrom;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(status: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        ServingStatus::try_from(*status)
+            .unwrap_or(ServingStatus::Unknown)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        ServingStatus::deserialize(deserializer).map(|status| status as i32)
+    }
+}
+
+/// Shared, runtime updatable serving status of the services of an application.
+///
+/// Cloning yields a handle to the same statuses.
+#[derive(Clone, Debug)]
+pub struct HealthReporter {
+    state: Arc<RwLock<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    statuses: HashMap<String, ServingStatus>,
+    /// The tasks polling a [`HealthReporter::watch`] stream, woken on the next change.
+    watchers: Vec<Waker>,
+}
+
+impl State {
+    fn notify(&mut self) {
+        for waker in self.watchers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for HealthReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthReporter {
+    /// Create a reporter with the overall server status set to [`ServingStatus::Serving`].
+    pub fn new() -> Self {
+        let reporter = Self {
+            state: Default::default(),
+        };
+        reporter.set_status("", ServingStatus::Serving);
+        reporter
+    }
+
+    /// Set the status of `service`, e.g. the [`NamedService::NAME`] of a service, or the empty
+    /// string for the overall server status.
+    pub fn set_status(&self, service: impl Into<String>, status: ServingStatus) {
+        let mut state = self.write();
+        state.statuses.insert(service.into(), status);
+        state.notify();
+    }
+
+    /// Forget the status of `service`, so checking it fails with [`Code::NotFound`].
+    pub fn clear_status(&self, service: &str) {
+        let mut state = self.write();
+        if state.statuses.remove(service).is_some() {
+            state.notify();
+        }
+    }
+
+    /// The status of `service`, if set.
+    pub fn status(&self, service: &str) -> Option<ServingStatus> {
+        self.state
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .statuses
+            .get(service)
+            .copied()
+    }
+
+    /// Stream the status of `service`: the current status, then each change of it.
+    ///
+    /// Unset statuses are reported as [`ServingStatus::ServiceUnknown`]. The stream never ends.
+    pub fn watch(&self, service: impl Into<String>) -> impl Stream<Item = ServingStatus> + Send {
+        let reporter = self.clone();
+        let service = service.into();
+        let mut last = None;
+        futures_util::stream::poll_fn(move |cx| {
+            let mut state = reporter.write();
+            let status = state
+                .statuses
+                .get(&service)
+                .copied()
+                .unwrap_or(ServingStatus::ServiceUnknown);
+            if last != Some(status) {
+                last = Some(status);
+                return Poll::Ready(Some(status));
+            }
+            if !state.watchers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.watchers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, State> {
+        self.state.write().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// The `grpc.health.v1.Health` service reporting the statuses of a [`HealthReporter`].
+///
+/// It can be constructed from any application type `A` exposing its reporter via `AsRef`.
+pub struct HealthService<A = ()> {
+    reporter: HealthReporter,
+    _marker: PhantomData<fn() -> A>,
+}
+
+impl<A> HealthService<A> {
+    pub fn new(reporter: HealthReporter) -> Self {
+        Self {
+            reporter,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Check the status of the requested service.
+    pub fn check(&self, request: HealthCheckRequest) -> Result<HealthCheckResponse, ProtoError> {
+        match self.reporter.status(&request.service) {
+            Some(status) => Ok(HealthCheckResponse {
+                status: status as i32,
+            }),
+            None => Err(ProtoError::with_code(
+                Code::NotFound,
+                alloc::format!("Unknown service: {}", request.service),
+            )),
+        }
+    }
+
+    /// Watch the status of the requested service, see [`HealthReporter::watch`].
+    pub fn watch(
+        &self,
+        request: HealthCheckRequest,
+    ) -> impl Stream<Item = HealthCheckResponse> + Send {
+        self.reporter
+            .watch(request.service)
+            .map(|status| HealthCheckResponse {
+                status: status as i32,
+            })
+    }
+}
+
+impl<A> Clone for HealthService<A> {
+    fn clone(&self) -> Self {
+        Self::new(self.reporter.clone())
+    }
+}
+
+impl<A: AsRef<HealthReporter>> From<A> for HealthService<A> {
+    fn from(app: A) -> Self {
+        Self::new(app.as_ref().clone())
+    }
+}
+
+impl<A> NamedService for HealthService<A> {
+    const NAME: &'static str = "grpc.health.v1.Health";
+}
+
+impl<A> Service for HealthService<A> {
+    type Methods = &'static [&'static str];
+
+    fn methods() -> Self::Methods {
+        &["grpc.health.v1.Health.Check", "grpc.health.v1.Health.Watch"]
+    }
+
+    async fn dispatch_request(
+        self,
+        _context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        match path {
+            "grpc.health.v1.Health.Check" => {
+                let request = encoding.decode(data.as_ref())?;
+                let response = self
+                    .check(request)
+                    .map_err(|err| DispatchError::Handler(err.into()))?;
+                encoding.encode(&response)
+            }
+            _ => Err(DispatchError::MethodNotFound(path.into())),
+        }
+    }
+
+    async fn dispatch_stream_request<'a>(
+        self,
+        _context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        match path {
+            "grpc.health.v1.Health.Watch" => {
+                let request = encoding.decode(data.as_ref())?;
+                Ok(encode_response_stream(
+                    self.watch(request).map(Ok),
+                    move |response| Ok(encoding.encode(&response)?),
+                ))
+            }
+            _ => Err(DispatchError::MethodNotFound(path.into())),
+        }
+    }
+}
+
+impl<A> SendService for HealthService<A> {
+    async fn dispatch_request(
+        self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]> + Send,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        Service::dispatch_request(self, context, path, data, encoding).await
+    }
+
+    async fn dispatch_stream_request<'a>(
+        self,
+        _context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]> + Send,
+        encoding: Encoding,
+    ) -> Result<SendResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        match path {
+            "grpc.health.v1.Health.Watch" => {
+                let request = encoding.decode(data.as_ref())?;
+                Ok(encode_send_response_stream(
+                    self.watch(request).map(Ok),
+                    move |response| Ok(encoding.encode(&response)?),
+                ))
+            }
+            _ => Err(DispatchError::MethodNotFound(path.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+
+    const CHECK: &str = "grpc.health.v1.Health.Check";
+    const WATCH: &str = "grpc.health.v1.Health.Watch";
+
+    async fn check(service: &HealthService, name: &str) -> Result<Vec<u8>, DispatchError> {
+        let request = alloc::format!(r#"{{"service":"{}"}}"#, name);
+        Service::dispatch_request(
+            service.clone(),
+            &Context::new(),
+            CHECK,
+            request,
+            Encoding::Json,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let reporter = HealthReporter::new();
+        let service = HealthService::new(reporter.clone());
+        assert_eq!(
+            check(&service, "").await.unwrap(),
+            br#"{"status":"SERVING"}"#
+        );
+
+        reporter.set_status("echo.v1.Echo", ServingStatus::Serving);
+        let response = check(&service, "echo.v1.Echo").await.unwrap();
+        assert_eq!(response, br#"{"status":"SERVING"}"#);
+        reporter.set_status("echo.v1.Echo", ServingStatus::NotServing);
+        let response = check(&service, "echo.v1.Echo").await.unwrap();
+        assert_eq!(response, br#"{"status":"NOT_SERVING"}"#);
+
+        reporter.clear_status("echo.v1.Echo");
+        let err = check(&service, "echo.v1.Echo").await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+        let err = check(&service, "other.v1.Other").await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let reporter = HealthReporter::new();
+        let service = HealthService::<()>::new(reporter.clone());
+        let context = Context::new();
+        let mut responses = SendService::dispatch_stream_request(
+            service,
+            &context,
+            WATCH,
+            br#"{"service":"echo.v1.Echo"}"#,
+            Encoding::Json,
+        )
+        .await
+        .unwrap();
+        let mut next = || {
+            responses
+                .next()
+                .now_or_never()
+                .map(|frame| frame.unwrap().unwrap())
+        };
+
+        // Unknown services are watched rather than rejected, as they may be registered later.
+        assert_eq!(next().unwrap(), br#"{"status":"SERVICE_UNKNOWN"}"#);
+        assert_eq!(next(), None);
+        reporter.set_status("echo.v1.Echo", ServingStatus::Serving);
+        assert_eq!(next().unwrap(), br#"{"status":"SERVING"}"#);
+        // Only changes of the status are streamed.
+        reporter.set_status("echo.v1.Echo", ServingStatus::Serving);
+        reporter.set_status("other.v1.Other", ServingStatus::NotServing);
+        assert_eq!(next(), None);
+        reporter.set_status("echo.v1.Echo", ServingStatus::NotServing);
+        assert_eq!(next().unwrap(), br#"{"status":"NOT_SERVING"}"#);
+        reporter.clear_status("echo.v1.Echo");
+        assert_eq!(next().unwrap(), br#"{"status":"SERVICE_UNKNOWN"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_watch_wakes() {
+        let reporter = HealthReporter::new();
+        let mut statuses = reporter.watch("echo.v1.Echo");
+        assert_eq!(statuses.next().await, Some(ServingStatus::ServiceUnknown));
+        let waiting = tokio::spawn(async move { statuses.next().await });
+        tokio::task::yield_now().await;
+        reporter.set_status("echo.v1.Echo", ServingStatus::Serving);
+        assert_eq!(waiting.await.unwrap(), Some(ServingStatus::Serving));
+    }
+}
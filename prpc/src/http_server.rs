@@ -0,0 +1,253 @@
+//! Reference HTTP/1 server for prpc services, built on [`SendTowerService`].
+//!
+//! Methods are served under `/prpc/` by default:
+//!
+//! - `POST /prpc/{method}` with a protobuf body, or a JSON body with `Content-Type:
+//!   application/json`, responds in the same encoding. Other content types are rejected with
+//!   `415 Unsupported Media Type`.
+//! - `GET /prpc/{method}?a=b` decodes the query string and responds with JSON.
+//!
+//! Errors respond with the status code mapped from the [`DispatchError`](crate::server::DispatchError)
+//! and a [`ProtoError`](crate::server::ProtoError) body.
+//!
+//! Services must implement [`SendService`], e.g. the servers generated with
+//! `Builder::send_futures` of `prpc-build`, as [`serve`] spawns a task per connection. To mount
+//! the services in an existing `axum` or `hyper` server instead, use the tower service returned
+//! by [`handler`].
+
+use crate::server::{Context, DeadlineService, SendService, Service};
+use crate::tower::{SendBoxFuture, SendTowerService, TowerService};
+use core::convert::Infallible;
+use core::task::Poll;
+use core::time::Duration;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+
+/// The path prefix methods are served under by default.
+pub const DEFAULT_PREFIX: &str = "/prpc/";
+
+/// The delay before accepting connections again after accepting one failed, e.g. as the process
+/// ran out of file descriptors.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// A tower service serving `service` under [`DEFAULT_PREFIX`], enforcing request deadlines with
+/// a [`DeadlineService`].
+///
+/// The service can be mounted in an `axum` router, e.g. with
+/// `Router::fallback_service(handler(service))` as the full URI path is matched, or served on
+/// `hyper` connections.
+pub fn handler<S>(service: S) -> SendTowerService<DeadlineService<S>> {
+    TowerService::new(DeadlineService::new(service))
+        .with_prefix(DEFAULT_PREFIX)
+        .into()
+}
+
+/// Serve `service` under [`DEFAULT_PREFIX`] on `listener`, see [`handler`].
+pub async fn serve<S>(listener: TcpListener, service: S) -> io::Result<()>
+where
+    S: Service + SendService + Clone + Send + Sync + 'static,
+{
+    serve_with(listener, handler(service)).await
+}
+
+/// Like [`serve`], with a custom configured [`SendTowerService`].
+///
+/// Each connection is served on a spawned task. Failing to accept a connection is logged and
+/// accepting resumes after a short delay, so the server only stops when the future is dropped.
+pub async fn serve_with<S>(listener: TcpListener, service: SendTowerService<S>) -> io::Result<()>
+where
+    S: Service + SendService + Clone + Send + Sync + 'static,
+{
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to accept a connection");
+                tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                continue;
+            }
+        };
+        // Errors of a single connection only affect that client.
+        tokio::spawn(serve_connection(stream, remote_addr, service.clone()));
+    }
+}
+
+/// Serve HTTP/1 requests of a single connection from `remote_addr`.
+///
+/// The remote address is passed to the service in [`Context::remote_addr`].
+pub async fn serve_connection<S, I>(
+    io: I,
+    remote_addr: SocketAddr,
+    service: SendTowerService<S>,
+) -> Result<(), hyper::Error>
+where
+    S: Service + SendService + Clone + Send + Sync + 'static,
+    I: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let service = WithRemoteAddr {
+        service,
+        remote_addr,
+    };
+    http1::Builder::new()
+        .serve_connection(TokioIo::new(io), TowerToHyperService::new(service))
+        .await
+}
+
+/// Attaches the remote address of the connection to each request as a [`Context`].
+#[derive(Clone)]
+struct WithRemoteAddr<S> {
+    service: SendTowerService<S>,
+    remote_addr: SocketAddr,
+}
+
+impl<S> tower_service::Service<hyper::Request<Incoming>> for WithRemoteAddr<S>
+where
+    S: Service + SendService + Clone + Send + Sync + 'static,
+{
+    type Response =
+        <SendTowerService<S> as tower_service::Service<hyper::Request<Incoming>>>::Response;
+    type Error = Infallible;
+    type Future = SendBoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut core::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        tower_service::Service::<hyper::Request<Incoming>>::poll_ready(&mut self.service, cx)
+    }
+
+    fn call(&mut self, mut request: hyper::Request<Incoming>) -> Self::Future {
+        let context = request
+            .extensions_mut()
+            .remove::<Context>()
+            .unwrap_or_default()
+            .with_remote_addr(self.remote_addr.to_string());
+        request.extensions_mut().insert(context);
+        self.service.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{DispatchError, Encoding, SendResponseStream};
+    use core::future::Future;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service for Echo {
+        type Methods = &'static [&'static str];
+
+        fn methods() -> Self::Methods {
+            &["Echo.Say", "Echo.Peer"]
+        }
+
+        async fn dispatch_request(
+            self,
+            context: &Context,
+            path: &str,
+            data: impl AsRef<[u8]>,
+            encoding: Encoding,
+        ) -> Result<Vec<u8>, DispatchError> {
+            SendService::dispatch_request(self, context, path, data.as_ref().to_vec(), encoding)
+                .await
+        }
+    }
+
+    impl SendService for Echo {
+        fn dispatch_request(
+            self,
+            context: &Context,
+            path: &str,
+            data: impl AsRef<[u8]> + Send,
+            _encoding: Encoding,
+        ) -> impl Future<Output = Result<Vec<u8>, DispatchError>> + Send {
+            let result = match path {
+                "Echo.Say" => Ok(data.as_ref().to_vec()),
+                "Echo.Peer" => Ok(context.remote_addr.clone().unwrap_or_default().into_bytes()),
+                _ => Err(DispatchError::MethodNotFound(path.into())),
+            };
+            async move { result }
+        }
+
+        async fn dispatch_stream_request<'a>(
+            self,
+            _context: &'a Context,
+            path: &str,
+            _data: impl AsRef<[u8]> + Send,
+            _encoding: Encoding,
+        ) -> Result<SendResponseStream<'a>, DispatchError>
+        where
+            Self: 'a,
+        {
+            Err(DispatchError::MethodNotFound(path.into()))
+        }
+    }
+
+    async fn request(addr: SocketAddr, head: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "{head}\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serve_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = serve(listener, Echo);
+        let client = async {
+            let response = request(
+                addr,
+                "POST /prpc/Echo.Say HTTP/1.1\r\nContent-Type: application/json",
+                r#"{"a":1}"#,
+            )
+            .await;
+            assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+            assert!(response.contains("content-type: application/json"));
+            assert!(response.ends_with(r#"{"a":1}"#));
+
+            let response = request(addr, "POST /prpc/Echo.Say HTTP/1.1", "\x08\x01").await;
+            assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+            assert!(response.contains("content-type: application/x-protobuf"));
+            assert!(response.ends_with("\x08\x01"));
+
+            let response = request(
+                addr,
+                "POST /prpc/Echo.Say HTTP/1.1\r\nContent-Type: text/plain",
+                "a",
+            )
+            .await;
+            assert!(response.starts_with("HTTP/1.1 415"), "{}", response);
+
+            let response = request(addr, "GET /prpc/Echo.Say?a=b HTTP/1.1", "").await;
+            assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+            assert!(response.ends_with("a=b"));
+
+            let response = request(addr, "GET /prpc/Echo.Peer HTTP/1.1", "").await;
+            assert!(response.contains("\r\n\r\n127.0.0.1:"), "{}", response);
+
+            let response = request(addr, "GET /prpc/Echo.Missing HTTP/1.1", "").await;
+            assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+            assert!(response.contains(r#""code":"#), "{}", response);
+
+            let response = request(addr, "GET /other HTTP/1.1", "").await;
+            assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        };
+        tokio::select! {
+            result = server => panic!("server exited: {:?}", result),
+            _ = client => {}
+        }
+    }
+}
@@ -5,9 +5,24 @@ extern crate alloc;
 
 use alloc::vec::Vec;
 
+pub use futures_core::Stream;
 pub use prost::Message;
 
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
+#[cfg(feature = "health")]
+pub mod health;
+#[cfg(feature = "http-server")]
+pub mod http_server;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "reflection")]
+pub mod reflection;
 pub mod serde_helpers;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "tracing")]
+pub mod trace;
 
 pub use serde_json;
 pub use serde_qs;
@@ -16,38 +31,504 @@ pub mod server {
     use super::*;
     pub use anyhow::Error;
 
+    use alloc::boxed::Box;
     use core::marker::PhantomData;
+    use core::pin::Pin;
     use derive_more::Display;
+    use futures_util::StreamExt;
+    use parity_scale_codec::{Decode, DecodeAll, Encode};
+    use serde::{de::DeserializeOwned, Serialize};
+
+    mod authorizer;
+    mod context;
+    #[cfg(feature = "std")]
+    mod deadline;
+    mod dyn_service;
+    mod encoding;
+    mod limits;
+    #[cfg(feature = "std")]
+    mod metrics;
+    mod middleware;
+    mod paths;
+    #[cfg(feature = "std")]
+    mod registry;
+    mod send;
+
+    pub use authorizer::{Authorizer, SendAuthorizer};
+    pub use context::Context;
+    #[cfg(feature = "deadline")]
+    pub use deadline::DeadlineService;
+    #[cfg(feature = "std")]
+    pub use deadline::{format_timeout, parse_timeout, TIMEOUT_HEADER};
+    pub use dyn_service::{
+        BoxFuture, BoxStream, DynService, FrameStream, SendBoxFuture, SendBoxStream, SendDynService,
+    };
+    pub use encoding::Encoding;
+    pub use limits::Limits;
+    #[cfg(feature = "std")]
+    pub use metrics::{CallMetrics, Metrics, MetricsMiddleware, UNKNOWN_METHOD};
+    pub use middleware::{Middleware, MiddlewareService, Request};
+    pub use paths::MethodPaths;
+    #[cfg(feature = "std")]
+    pub use registry::ServiceRegistry;
+    pub use send::{encode_send_response_stream, SendResponseStream, SendService};
+
+    /// Status codes of RPC errors, following the gRPC status codes.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Code {
+        Ok = 0,
+        Cancelled = 1,
+        Unknown = 2,
+        InvalidArgument = 3,
+        DeadlineExceeded = 4,
+        NotFound = 5,
+        AlreadyExists = 6,
+        PermissionDenied = 7,
+        ResourceExhausted = 8,
+        FailedPrecondition = 9,
+        Aborted = 10,
+        OutOfRange = 11,
+        Unimplemented = 12,
+        Internal = 13,
+        Unavailable = 14,
+        DataLoss = 15,
+        Unauthenticated = 16,
+    }
+
+    impl Code {
+        /// The HTTP status code to respond with for this code, as mapped by grpc-gateway.
+        pub fn http_status(self) -> u16 {
+            match self {
+                Code::Ok => 200,
+                Code::Cancelled => 499,
+                Code::Unknown => 500,
+                Code::InvalidArgument => 400,
+                Code::DeadlineExceeded => 504,
+                Code::NotFound => 404,
+                Code::AlreadyExists => 409,
+                Code::PermissionDenied => 403,
+                Code::ResourceExhausted => 429,
+                Code::FailedPrecondition => 400,
+                Code::Aborted => 409,
+                Code::OutOfRange => 400,
+                Code::Unimplemented => 501,
+                Code::Internal => 500,
+                Code::Unavailable => 503,
+                Code::DataLoss => 500,
+                Code::Unauthenticated => 401,
+            }
+        }
+    }
+
+    /// Structured detail attached to a [`ProtoError`]. Wire compatible with `google.protobuf.Any`.
+    #[derive(Clone, PartialEq, Message, Encode, Decode)]
+    pub struct ErrorDetail {
+        #[prost(string, tag = "1")]
+        pub type_url: ::prost::alloc::string::String,
+        #[prost(bytes = "vec", tag = "2")]
+        pub value: ::prost::alloc::vec::Vec<u8>,
+    }
 
     /// The final Error type of RPCs to be serialized to protobuf.
-    #[derive(Display, Message)]
+    ///
+    /// Handlers can return a `ProtoError` wrapped in an [`Error`] to choose the status code seen by
+    /// the caller, e.g. `Err(ProtoError::with_code(Code::NotFound, "no such key").into())`.
+    ///
+    /// Peers predating status codes send errors without a code, decoded as `0`, so
+    /// [`ProtoError::code`] reports [`Code::Unknown`] rather than [`Code::Ok`] for them.
+    #[derive(Display, Clone, PartialEq, Message, Encode, Decode)]
+    #[display("{message}")]
     pub struct ProtoError {
         #[prost(string, tag = "1")]
         pub message: ::prost::alloc::string::String,
+        /// The [`Code`] of the error, see [`ProtoError::code`]. Encoded as the enumeration on the
+        /// wire.
+        #[prost(int32, tag = "2")]
+        pub code: i32,
+        #[prost(message, repeated, tag = "3")]
+        pub details: ::prost::alloc::vec::Vec<ErrorDetail>,
     }
 
     impl ProtoError {
         pub fn new(message: impl Into<String>) -> ProtoError {
+            Self::with_code(Code::Unknown, message)
+        }
+
+        pub fn with_code(code: Code, message: impl Into<String>) -> ProtoError {
             ProtoError {
                 message: message.into(),
+                code: code as i32,
+                details: Vec::new(),
             }
         }
+
+        /// The status code of the error. A missing code, as sent by older peers, or a code
+        /// unknown to this version is [`Code::Unknown`], as an error never has [`Code::Ok`].
+        pub fn code(&self) -> Code {
+            use core::convert::TryFrom;
+            match Code::try_from(self.code) {
+                Ok(Code::Ok) | Err(_) => Code::Unknown,
+                Ok(code) => code,
+            }
+        }
+
+        /// Attach a structured detail message identified by `type_url`.
+        pub fn with_detail(mut self, type_url: impl Into<String>, detail: &impl Message) -> Self {
+            self.details.push(ErrorDetail {
+                type_url: type_url.into(),
+                value: detail.encode_to_vec(),
+            });
+            self
+        }
+
+        /// The HTTP status code to respond with for this error.
+        pub fn http_status(&self) -> u16 {
+            self.code().http_status()
+        }
+    }
+
+    impl core::error::Error for ProtoError {}
+
+    impl Serialize for ErrorDetail {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeStruct;
+            let mut detail = serializer.serialize_struct("ErrorDetail", 2)?;
+            detail.serialize_field("type_url", &self.type_url)?;
+            detail.serialize_field("value", &hex::encode(&self.value))?;
+            detail.end()
+        }
+    }
+
+    impl Serialize for ProtoError {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeStruct;
+            let mut error = serializer.serialize_struct("ProtoError", 3)?;
+            error.serialize_field("message", &self.message)?;
+            error.serialize_field("code", &self.code)?;
+            error.serialize_field("details", &self.details)?;
+            error.end()
+        }
+    }
+
+    /// Find the [`ProtoError`] carried by the error chain, if any.
+    fn find_proto_error(error: &Error) -> Option<&ProtoError> {
+        error
+            .chain()
+            .find_map(|err| err.downcast_ref::<ProtoError>())
+    }
+
+    /// The code of the first [`ProtoError`] or [`DispatchError`] of the error chain, e.g. of a
+    /// request frame of a client stream failing to decode.
+    pub(crate) fn error_code(error: &Error) -> Option<Code> {
+        error.chain().find_map(|err| {
+            if let Some(err) = err.downcast_ref::<ProtoError>() {
+                Some(err.code())
+            } else {
+                err.downcast_ref::<DispatchError>().map(DispatchError::code)
+            }
+        })
+    }
+
+    impl From<Error> for ProtoError {
+        /// Use the [`ProtoError`] carried by the error chain if any, otherwise report the error
+        /// with the code of the [`DispatchError`] it carries, or as [`Code::Unknown`].
+        fn from(error: Error) -> Self {
+            match find_proto_error(&error) {
+                Some(err) => err.clone(),
+                None => ProtoError::with_code(
+                    error_code(&error).unwrap_or(Code::Unknown),
+                    alloc::format!("{error:#}"),
+                ),
+            }
+        }
+    }
+
+    /// Error returned when dispatching a request to a [`Service`].
+    #[derive(Debug, Display)]
+    pub enum DispatchError {
+        /// No service matches the request path.
+        #[display("Service not found: {_0}")]
+        ServiceNotFound(String),
+        /// The service has no method matching the request path.
+        #[display("Method not found: {_0}")]
+        MethodNotFound(String),
+        /// The request body could not be decoded.
+        #[display("Failed to decode request: {_0:#}")]
+        Decode(Error),
+        /// The handler returned an error.
+        #[display("{_0:#}")]
+        Handler(Error),
+        /// The response could not be encoded.
+        #[display("Failed to encode response: {_0:#}")]
+        Encode(Error),
+        /// The caller is not authorized to call the method.
+        #[display("Permission denied: {_0:#}")]
+        PermissionDenied(Error),
+        /// The service does not support the encoding of the request.
+        #[display("Unsupported encoding: {_0:?}")]
+        UnsupportedEncoding(Encoding),
+        /// The content type of the request has no [`Encoding`].
+        #[display("Unsupported content type: {_0}")]
+        UnsupportedContentType(String),
+        /// The deadline of the request passed before the response was ready.
+        #[display("Deadline exceeded")]
+        DeadlineExceeded,
+        /// The request exceeds the [`Limits`] of the method.
+        #[display("Request limit exceeded: {_0}")]
+        LimitExceeded(String),
+    }
+
+    impl DispatchError {
+        /// The status code reported to the caller.
+        pub fn code(&self) -> Code {
+            match self {
+                DispatchError::ServiceNotFound(_) | DispatchError::MethodNotFound(_) => {
+                    Code::Unimplemented
+                }
+                DispatchError::Decode(_) => Code::InvalidArgument,
+                DispatchError::Handler(err) => error_code(err).unwrap_or(Code::Unknown),
+                DispatchError::PermissionDenied(err) => find_proto_error(err)
+                    .map(|err| err.code())
+                    .unwrap_or(Code::PermissionDenied),
+                DispatchError::Encode(_) => Code::Internal,
+                DispatchError::UnsupportedEncoding(_)
+                | DispatchError::UnsupportedContentType(_) => Code::Unimplemented,
+                DispatchError::DeadlineExceeded => Code::DeadlineExceeded,
+                DispatchError::LimitExceeded(_) => Code::ResourceExhausted,
+            }
+        }
+
+        /// The HTTP status code to respond with. Unknown services and methods map to 404,
+        /// exceeded request limits to 413 and unsupported encodings to 415.
+        pub fn http_status(&self) -> u16 {
+            match self {
+                DispatchError::ServiceNotFound(_) | DispatchError::MethodNotFound(_) => 404,
+                DispatchError::LimitExceeded(_) => 413,
+                DispatchError::UnsupportedEncoding(_)
+                | DispatchError::UnsupportedContentType(_) => 415,
+                _ => self.code().http_status(),
+            }
+        }
+    }
+
+    impl core::error::Error for DispatchError {
+        fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+            match self {
+                DispatchError::ServiceNotFound(_)
+                | DispatchError::MethodNotFound(_)
+                | DispatchError::LimitExceeded(_)
+                | DispatchError::UnsupportedEncoding(_)
+                | DispatchError::UnsupportedContentType(_)
+                | DispatchError::DeadlineExceeded => None,
+                DispatchError::Decode(err)
+                | DispatchError::Handler(err)
+                | DispatchError::PermissionDenied(err)
+                | DispatchError::Encode(err) => Some(err.as_ref()),
+            }
+        }
+    }
+
+    impl From<DispatchError> for ProtoError {
+        fn from(error: DispatchError) -> Self {
+            match error {
+                DispatchError::Handler(err) => err.into(),
+                DispatchError::PermissionDenied(err) if find_proto_error(&err).is_some() => {
+                    err.into()
+                }
+                error => ProtoError::with_code(error.code(), alloc::format!("{error}")),
+            }
+        }
+    }
+
+    /// Decode a protobuf request body.
+    pub fn decode_message<M: Message + Default>(data: &[u8]) -> Result<M, DispatchError> {
+        M::decode(data).map_err(|err| DispatchError::Decode(Error::msg(err)))
+    }
+
+    /// Decode a JSON request body, or a query string if `query` is set. An empty body decodes to
+    /// the default message.
+    pub fn decode_json<M: DeserializeOwned + Default>(
+        data: &[u8],
+        query: bool,
+    ) -> Result<M, DispatchError> {
+        if data.is_empty() {
+            Ok(Default::default())
+        } else if query {
+            serde_qs::from_bytes(data).map_err(|err| DispatchError::Decode(err.into()))
+        } else {
+            serde_json::from_slice(data).map_err(|err| DispatchError::Decode(err.into()))
+        }
+    }
+
+    /// Encode a JSON response body.
+    pub fn encode_json(response: &impl Serialize) -> Result<Vec<u8>, DispatchError> {
+        serde_json::to_vec(response).map_err(|err| DispatchError::Encode(err.into()))
+    }
+
+    /// Decode a CBOR request body. An empty body decodes to the default message.
+    #[cfg(feature = "cbor")]
+    pub fn decode_cbor<M: DeserializeOwned + Default>(data: &[u8]) -> Result<M, DispatchError> {
+        if data.is_empty() {
+            return Ok(Default::default());
+        }
+        ciborium::from_reader(data).map_err(|err| DispatchError::Decode(err.into()))
+    }
+
+    /// Encode a CBOR response body.
+    #[cfg(feature = "cbor")]
+    pub fn encode_cbor(response: &impl Serialize) -> Result<Vec<u8>, DispatchError> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(response, &mut buf)
+            .map_err(|err| DispatchError::Encode(err.into()))?;
+        Ok(buf)
+    }
+
+    /// Decode a MessagePack request body. An empty body decodes to the default message.
+    #[cfg(feature = "msgpack")]
+    pub fn decode_msgpack<M: DeserializeOwned + Default>(data: &[u8]) -> Result<M, DispatchError> {
+        if data.is_empty() {
+            return Ok(Default::default());
+        }
+        rmp_serde::from_slice(data).map_err(|err| DispatchError::Decode(err.into()))
+    }
+
+    /// Encode a MessagePack response body, with structs encoded as maps keyed by field name.
+    #[cfg(feature = "msgpack")]
+    pub fn encode_msgpack(response: &impl Serialize) -> Result<Vec<u8>, DispatchError> {
+        rmp_serde::to_vec_named(response).map_err(|err| DispatchError::Encode(err.into()))
+    }
+
+    /// Decode a SCALE request body. The whole body must be consumed.
+    pub fn decode_scale<M: Decode>(data: &[u8]) -> Result<M, DispatchError> {
+        M::decode_all(&mut &data[..]).map_err(|err| DispatchError::Decode(Error::msg(err)))
+    }
+
+    /// Encode a SCALE response body.
+    pub fn encode_scale(response: &impl Encode) -> Vec<u8> {
+        response.encode()
+    }
+
+    /// Stream of encoded response frames produced by a streaming RPC.
+    ///
+    /// Each item is a single encoded message. How frames are delimited on the wire is up to the
+    /// transport.
+    pub type ResponseStream<'a> = Pin<Box<dyn Stream<Item = Result<Vec<u8>, Error>> + 'a>>;
+
+    /// Stream of decoded request messages passed to client-streaming and bidirectional handlers.
+    pub type RequestStream<'a, M> = Pin<Box<dyn Stream<Item = Result<M, Error>> + 'a>>;
+
+    /// Decode each encoded request frame with `decode` and box it into a [`RequestStream`].
+    pub fn decode_request_stream<'a, S, M, F>(frames: S, mut decode: F) -> RequestStream<'a, M>
+    where
+        S: Stream<Item = Result<Vec<u8>, Error>> + 'a,
+        F: FnMut(Vec<u8>) -> Result<M, Error> + 'a,
+    {
+        Box::pin(frames.map(move |frame| frame.and_then(&mut decode)))
+    }
+
+    /// A [`ResponseStream`] yielding a single frame, used as the response of client-streaming RPCs.
+    pub fn single_response<'a>(frame: Vec<u8>) -> ResponseStream<'a> {
+        Box::pin(futures_util::stream::once(async move { Ok(frame) }))
+    }
+
+    /// Encode each message of a response stream with `encode` and box it into a [`ResponseStream`].
+    pub fn encode_response_stream<'a, S, M, F>(stream: S, mut encode: F) -> ResponseStream<'a>
+    where
+        S: Stream<Item = Result<M, Error>> + 'a,
+        F: FnMut(M) -> Result<Vec<u8>, Error> + 'a,
+    {
+        Box::pin(stream.map(move |item| item.and_then(&mut encode)))
+    }
+
+    /// Wrap the error of a handler with a typed error into an [`Error`] carrying its
+    /// [`ProtoError`], so the caller receives the code and message chosen by the error type.
+    pub fn handler_error(error: impl Into<ProtoError>) -> Error {
+        Error::new(error.into())
+    }
+
+    /// Map the errors of a response stream with a typed error with [`handler_error`].
+    pub fn handler_error_stream<'a, M, E: Into<ProtoError>>(
+        stream: impl Stream<Item = Result<M, E>> + 'a,
+    ) -> impl Stream<Item = Result<M, Error>> + 'a {
+        stream.map(|item| item.map_err(handler_error))
     }
 
     pub trait NamedService: Service {
+        /// The path prefix of the service methods, e.g. `my.pkg.Service` when the package is
+        /// emitted. Methods of the service are dispatched by `{NAME}.{Method}`.
         const NAME: &'static str;
     }
 
+    /// Find the service whose name is the longest prefix of `path`.
+    ///
+    /// A name only matches whole path segments, and an empty name matches any path.
+    pub(crate) fn route_service<'a>(
+        path: &str,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&'a str> {
+        names
+            .into_iter()
+            .filter(|name| {
+                name.is_empty()
+                    || path
+                        .strip_prefix(*name)
+                        .is_some_and(|method| method.starts_with('.'))
+            })
+            .max_by_key(|name| name.len())
+    }
+
     pub trait Service {
         type Methods: AsRef<[&'static str]>;
         fn methods() -> Self::Methods;
+        /// The default timeout of the method at `path`, e.g. declared with a `@timeout`
+        /// annotation. Clients can only shorten it with an earlier deadline.
+        fn method_timeout(_path: &str) -> Option<core::time::Duration> {
+            None
+        }
+        /// The default timeout of the method at `path` for this service, defaulting to
+        /// [`Service::method_timeout`]. Services only knowing their methods at runtime, e.g. the
+        /// pointers to a [`DynService`], override it, so wrappers and transports should call it
+        /// rather than [`Service::method_timeout`].
+        fn timeout_for(&self, path: &str) -> Option<core::time::Duration> {
+            Self::method_timeout(path)
+        }
         async fn dispatch_request(
             self,
+            context: &Context,
             path: &str,
             data: impl AsRef<[u8]>,
-            json: bool,
-            query: bool,
-        ) -> Result<Vec<u8>, Error>;
+            encoding: Encoding,
+        ) -> Result<Vec<u8>, DispatchError>;
+        /// Dispatch a server-streaming request, returning the stream of encoded response frames.
+        async fn dispatch_stream_request<'a>(
+            self,
+            _context: &'a Context,
+            path: &str,
+            _data: impl AsRef<[u8]>,
+            _encoding: Encoding,
+        ) -> Result<ResponseStream<'a>, DispatchError>
+        where
+            Self: Sized + 'a,
+        {
+            Err(DispatchError::MethodNotFound(path.into()))
+        }
+        /// Dispatch a client-streaming or bidirectional request.
+        ///
+        /// `requests` yields the encoded request frames. Client-streaming RPCs respond with a
+        /// stream of exactly one frame.
+        async fn dispatch_client_stream_request<'a>(
+            self,
+            _context: &'a Context,
+            path: &str,
+            _requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+            _encoding: Encoding,
+        ) -> Result<ResponseStream<'a>, DispatchError>
+        where
+            Self: Sized + 'a,
+        {
+            Err(DispatchError::MethodNotFound(path.into()))
+        }
     }
 
     pub struct ComposedService<A, T> {
@@ -55,6 +536,12 @@ pub mod server {
         _marker: PhantomData<T>,
     }
 
+    impl<A: Clone, T> Clone for ComposedService<A, T> {
+        fn clone(&self) -> Self {
+            Self::new(self.app.clone())
+        }
+    }
+
     impl<T, A> ComposedService<A, T> {
         pub fn new(app: A) -> Self {
             Self {
@@ -82,12 +569,38 @@ pub mod server {
 
                 async fn dispatch_request(
                     self,
+                    _context: &Context,
                     path: &str,
                     _data: impl AsRef<[u8]>,
-                    _json: bool,
-                    _query: bool,
-                ) -> Result<Vec<u8>, Error> {
-                    anyhow::bail!("Service not found: {path}")
+                    _encoding: Encoding,
+                ) -> Result<Vec<u8>, DispatchError> {
+                    Err(DispatchError::ServiceNotFound(path.into()))
+                }
+
+                async fn dispatch_stream_request<'a>(
+                    self,
+                    _context: &'a Context,
+                    path: &str,
+                    _data: impl AsRef<[u8]>,
+                    _encoding: Encoding,
+                ) -> Result<ResponseStream<'a>, DispatchError>
+                where
+                    Self: 'a,
+                {
+                    Err(DispatchError::ServiceNotFound(path.into()))
+                }
+
+                async fn dispatch_client_stream_request<'a>(
+                    self,
+                    _context: &'a Context,
+                    path: &str,
+                    _requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+                    _encoding: Encoding,
+                ) -> Result<ResponseStream<'a>, DispatchError>
+                where
+                    Self: 'a,
+                {
+                    Err(DispatchError::ServiceNotFound(path.into()))
                 }
             }
         };
@@ -109,23 +622,83 @@ pub mod server {
                     methods
                 }
 
+                fn method_timeout(path: &str) -> Option<core::time::Duration> {
+                    let service_name = route_service(path, [$head::NAME, $($tail::NAME,)*])?;
+                    if service_name == $head::NAME {
+                        return $head::method_timeout(path);
+                    }
+                    $(
+                        if service_name == $tail::NAME {
+                            return $tail::method_timeout(path);
+                        }
+                    )*
+                    None
+                }
+
                 async fn dispatch_request(
                     self,
+                    context: &Context,
+                    path: &str,
+                    data: impl AsRef<[u8]>,
+                    encoding: Encoding,
+                ) -> Result<Vec<u8>, DispatchError> {
+                    let service_name = route_service(path, [$head::NAME, $($tail::NAME,)*])
+                        .ok_or_else(|| DispatchError::ServiceNotFound(path.into()))?;
+                    if service_name == $head::NAME {
+                        return $head::from(self.app).dispatch_request(context, path, data, encoding).await;
+                    }
+                    $(
+                        if service_name == $tail::NAME {
+                            return $tail::from(self.app).dispatch_request(context, path, data, encoding).await;
+                        }
+                    )*
+                    Err(DispatchError::ServiceNotFound(path.into()))
+                }
+
+                async fn dispatch_stream_request<'a>(
+                    self,
+                    context: &'a Context,
                     path: &str,
                     data: impl AsRef<[u8]>,
-                    json: bool,
-                    query: bool,
-                ) -> Result<Vec<u8>, Error> {
-                    let service_name = path.split('.').next().unwrap_or_default();
+                    encoding: Encoding,
+                ) -> Result<ResponseStream<'a>, DispatchError>
+                where
+                    Self: 'a,
+                {
+                    let service_name = route_service(path, [$head::NAME, $($tail::NAME,)*])
+                        .ok_or_else(|| DispatchError::ServiceNotFound(path.into()))?;
                     if service_name == $head::NAME {
-                        return $head::from(self.app).dispatch_request(path, data, json, query).await;
+                        return $head::from(self.app).dispatch_stream_request(context, path, data, encoding).await;
                     }
                     $(
                         if service_name == $tail::NAME {
-                            return $tail::from(self.app).dispatch_request(path, data, json, query).await;
+                            return $tail::from(self.app).dispatch_stream_request(context, path, data, encoding).await;
                         }
                     )*
-                    anyhow::bail!("Service not found: {service_name}")
+                    Err(DispatchError::ServiceNotFound(path.into()))
+                }
+
+                async fn dispatch_client_stream_request<'a>(
+                    self,
+                    context: &'a Context,
+                    path: &str,
+                    requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+                    encoding: Encoding,
+                ) -> Result<ResponseStream<'a>, DispatchError>
+                where
+                    Self: 'a,
+                {
+                    let service_name = route_service(path, [$head::NAME, $($tail::NAME,)*])
+                        .ok_or_else(|| DispatchError::ServiceNotFound(path.into()))?;
+                    if service_name == $head::NAME {
+                        return $head::from(self.app).dispatch_client_stream_request(context, path, requests, encoding).await;
+                    }
+                    $(
+                        if service_name == $tail::NAME {
+                            return $tail::from(self.app).dispatch_client_stream_request(context, path, requests, encoding).await;
+                        }
+                    )*
+                    Err(DispatchError::ServiceNotFound(path.into()))
                 }
             }
 
@@ -135,21 +708,742 @@ pub mod server {
     }
 
     impl_service_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
+
+    // Implement `SendService` for the `ComposedService`s of `SendService`s, like
+    // `impl_service_for_tuple`.
+    macro_rules! impl_send_service_for_tuple {
+        () => {
+            impl<A: Send> SendService for ComposedService<A, ()> {
+                async fn dispatch_request(
+                    self,
+                    _context: &Context,
+                    path: &str,
+                    _data: impl AsRef<[u8]> + Send,
+                    _encoding: Encoding,
+                ) -> Result<Vec<u8>, DispatchError> {
+                    Err(DispatchError::ServiceNotFound(path.into()))
+                }
+
+                async fn dispatch_stream_request<'a>(
+                    self,
+                    _context: &'a Context,
+                    path: &str,
+                    _data: impl AsRef<[u8]> + Send,
+                    _encoding: Encoding,
+                ) -> Result<SendResponseStream<'a>, DispatchError>
+                where
+                    Self: 'a,
+                {
+                    Err(DispatchError::ServiceNotFound(path.into()))
+                }
+            }
+        };
+
+        ( $head:ident $(, $tail:ident)* $(,)*) => {
+            impl<A, $head, $( $tail, )*> SendService for ComposedService<A, ($head, $( $tail, )*)>
+            where
+                A: Send,
+                $head: NamedService + SendService + From<A> + Send,
+                $( $tail: NamedService + SendService + From<A> + Send, )*
+            {
+                fn dispatch_request(
+                    self,
+                    context: &Context,
+                    path: &str,
+                    data: impl AsRef<[u8]> + Send,
+                    encoding: Encoding,
+                ) -> impl core::future::Future<Output = Result<Vec<u8>, DispatchError>> + Send {
+                    async move {
+                        let service_name = route_service(path, [$head::NAME, $($tail::NAME,)*])
+                            .ok_or_else(|| DispatchError::ServiceNotFound(path.into()))?;
+                        if service_name == $head::NAME {
+                            return SendService::dispatch_request($head::from(self.app), context, path, data, encoding).await;
+                        }
+                        $(
+                            if service_name == $tail::NAME {
+                                return SendService::dispatch_request($tail::from(self.app), context, path, data, encoding).await;
+                            }
+                        )*
+                        Err(DispatchError::ServiceNotFound(path.into()))
+                    }
+                }
+
+                fn dispatch_stream_request<'a>(
+                    self,
+                    context: &'a Context,
+                    path: &str,
+                    data: impl AsRef<[u8]> + Send,
+                    encoding: Encoding,
+                ) -> impl core::future::Future<Output = Result<SendResponseStream<'a>, DispatchError>> + Send
+                where
+                    Self: 'a,
+                {
+                    async move {
+                        let service_name = route_service(path, [$head::NAME, $($tail::NAME,)*])
+                            .ok_or_else(|| DispatchError::ServiceNotFound(path.into()))?;
+                        if service_name == $head::NAME {
+                            return SendService::dispatch_stream_request($head::from(self.app), context, path, data, encoding).await;
+                        }
+                        $(
+                            if service_name == $tail::NAME {
+                                return SendService::dispatch_stream_request($tail::from(self.app), context, path, data, encoding).await;
+                            }
+                        )*
+                        Err(DispatchError::ServiceNotFound(path.into()))
+                    }
+                }
+            }
+
+            impl_send_service_for_tuple!($($tail,)*);
+        };
+    }
+
+    impl_send_service_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12);
 }
 
 pub mod client {
+    use alloc::boxed::Box;
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::time::Duration;
+    use futures_util::future::Either;
+    use futures_util::StreamExt;
+    use parity_scale_codec::{Decode, Encode};
     use serde::{de::DeserializeOwned, Serialize};
 
     use super::*;
+    pub use crate::server::Encoding;
+    #[cfg(feature = "std")]
+    pub use crate::server::{format_timeout, TIMEOUT_HEADER};
     pub use anyhow::Error;
+    pub use dyn_client::{
+        BoxFuture, BoxStream, DynRequestClient, SendBoxFuture, SendBoxStream, SendDynRequestClient,
+    };
+
+    mod dyn_client;
+
+    /// Stream of decoded messages returned by a streaming RPC.
+    pub type ResponseStream<'a, R> = Pin<Box<dyn Stream<Item = Result<R, Error>> + 'a>>;
+
+    /// Stream of decoded messages returned by a streaming RPC of a [`SendRequestClient`].
+    pub type SendResponseStream<'a, R> = Pin<Box<dyn Stream<Item = Result<R, Error>> + Send + 'a>>;
+
+    /// Take the only response of a client-streaming RPC.
+    pub async fn single_response<R>(
+        responses: impl Stream<Item = Result<R, Error>>,
+    ) -> Result<R, Error> {
+        let mut responses = core::pin::pin!(responses);
+        match responses.next().await {
+            Some(response) => response,
+            None => anyhow::bail!("Missing response of client-streaming RPC"),
+        }
+    }
+
+    /// Encode a request body in `encoding`, for transports implementing [`RequestClient`].
+    ///
+    /// [`Encoding::Query`] encodes the request as a URL query string.
+    pub fn encode_request<T: Message + Serialize>(
+        encoding: Encoding,
+        body: &T,
+    ) -> Result<Vec<u8>, Error> {
+        match encoding {
+            Encoding::Query => Ok(serde_qs::to_string(body)?.into_bytes()),
+            _ => Ok(encoding.encode(body)?),
+        }
+    }
+
+    /// Decode a response body in `encoding`, for transports implementing [`RequestClient`].
+    pub fn decode_response<R: Message + DeserializeOwned + Default>(
+        encoding: Encoding,
+        data: &[u8],
+    ) -> Result<R, Error> {
+        match encoding {
+            Encoding::Query => Ok(crate::server::decode_json(data, false)?),
+            _ => Ok(encoding.decode(data)?),
+        }
+    }
 
     /// Trait for RPC client to implement the underlying data transport.
     /// Required by the generated RPC client.
+    ///
+    /// Transports choose the wire [`Encoding`], e.g. with [`encode_request`] and
+    /// [`decode_response`]. Implementations written against the original signature of
+    /// [`RequestClient::request`], without the `R: Default` bound, still implement the trait.
+    ///
+    /// The `*_with_timeout` methods are called by the generated clients returned by
+    /// `with_timeout`, e.g. with the [`Context::remaining`](crate::server::Context::remaining)
+    /// time of the request being handled. Transports supporting deadlines override them to send
+    /// the timeout as a [`TIMEOUT_HEADER`] formatted with [`format_timeout`], so that the deadline
+    /// propagates to the server. The defaults drop the timeout.
     pub trait RequestClient {
         async fn request<T, R>(&self, path: &str, body: T) -> Result<R, Error>
         where
             T: Message + Serialize,
-            R: Message + DeserializeOwned;
+            R: Message + DeserializeOwned + Default;
+
+        /// Send a SCALE encoded request to a unary RPC and decode the SCALE encoded response.
+        ///
+        /// Used by clients generated with SCALE encoding enabled. Transports without SCALE
+        /// support can leave the default, which always fails.
+        async fn request_scale<T, R>(&self, path: &str, _body: T) -> Result<R, Error>
+        where
+            T: Encode,
+            R: Decode,
+        {
+            anyhow::bail!("SCALE encoding not supported by this client: {path}")
+        }
+
+        /// Send a request to a server-streaming RPC and receive the stream of responses.
+        ///
+        /// Transports without streaming support can leave the default, which always fails.
+        async fn request_stream<T, R>(
+            &self,
+            path: &str,
+            _body: T,
+        ) -> Result<ResponseStream<'_, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            anyhow::bail!("Streaming RPC not supported by this client: {path}")
+        }
+
+        /// Send a stream of requests to a client-streaming or bidirectional RPC and receive the
+        /// stream of responses. Client-streaming RPCs respond with exactly one message.
+        ///
+        /// Transports without streaming support can leave the default, which always fails.
+        async fn request_client_stream<'a, T, R>(
+            &'a self,
+            path: &str,
+            _requests: impl Stream<Item = T> + 'a,
+        ) -> Result<ResponseStream<'a, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            anyhow::bail!("Streaming RPC not supported by this client: {path}")
+        }
+
+        /// [`RequestClient::request`] with a timeout.
+        async fn request_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            _timeout: Duration,
+        ) -> Result<R, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            self.request(path, body).await
+        }
+
+        /// [`RequestClient::request_scale`] with a timeout.
+        async fn request_scale_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            _timeout: Duration,
+        ) -> Result<R, Error>
+        where
+            T: Encode,
+            R: Decode,
+        {
+            self.request_scale(path, body).await
+        }
+
+        /// [`RequestClient::request_stream`] with a timeout.
+        async fn request_stream_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            _timeout: Duration,
+        ) -> Result<ResponseStream<'_, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            self.request_stream(path, body).await
+        }
+
+        /// [`RequestClient::request_client_stream`] with a timeout.
+        async fn request_client_stream_with_timeout<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + 'a,
+            _timeout: Duration,
+        ) -> Result<ResponseStream<'a, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            self.request_client_stream(path, requests).await
+        }
+    }
+
+    /// Like [`RequestClient`], with `Send` futures and response streams so calls can be spawned
+    /// on multi-threaded runtimes. Required by the clients generated with `Builder::send_futures`
+    /// of `prpc-build`.
+    pub trait SendRequestClient {
+        fn request<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default;
+
+        /// See [`RequestClient::request_scale`].
+        fn request_scale<T, R>(
+            &self,
+            path: &str,
+            _body: T,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Encode + Send,
+            R: Decode,
+        {
+            async move { anyhow::bail!("SCALE encoding not supported by this client: {path}") }
+        }
+
+        /// See [`RequestClient::request_stream`].
+        fn request_stream<T, R>(
+            &self,
+            path: &str,
+            _body: T,
+        ) -> impl Future<Output = Result<SendResponseStream<'_, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            async move { anyhow::bail!("Streaming RPC not supported by this client: {path}") }
+        }
+
+        /// See [`RequestClient::request_client_stream`].
+        fn request_client_stream<'a, T, R>(
+            &'a self,
+            path: &str,
+            _requests: impl Stream<Item = T> + Send + 'a,
+        ) -> impl Future<Output = Result<SendResponseStream<'a, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            async move { anyhow::bail!("Streaming RPC not supported by this client: {path}") }
+        }
+
+        /// See [`RequestClient::request_with_timeout`].
+        fn request_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            _timeout: Duration,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            self.request(path, body)
+        }
+
+        /// See [`RequestClient::request_scale_with_timeout`].
+        fn request_scale_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            _timeout: Duration,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Encode + Send,
+            R: Decode,
+        {
+            self.request_scale(path, body)
+        }
+
+        /// See [`RequestClient::request_stream_with_timeout`].
+        fn request_stream_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            _timeout: Duration,
+        ) -> impl Future<Output = Result<SendResponseStream<'_, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            self.request_stream(path, body)
+        }
+
+        /// See [`RequestClient::request_client_stream_with_timeout`].
+        fn request_client_stream_with_timeout<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + Send + 'a,
+            _timeout: Duration,
+        ) -> impl Future<Output = Result<SendResponseStream<'a, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            self.request_client_stream(path, requests)
+        }
+    }
+
+    // Borrowed transports, for the generated clients returned by `with_timeout`.
+    impl<C: RequestClient + ?Sized> RequestClient for &C {
+        async fn request<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request(path, body).await
+        }
+
+        async fn request_scale<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+        where
+            T: Encode,
+            R: Decode,
+        {
+            (**self).request_scale(path, body).await
+        }
+
+        async fn request_stream<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> Result<ResponseStream<'_, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_stream(path, body).await
+        }
+
+        async fn request_client_stream<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + 'a,
+        ) -> Result<ResponseStream<'a, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_client_stream(path, requests).await
+        }
+
+        async fn request_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            timeout: Duration,
+        ) -> Result<R, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_with_timeout(path, body, timeout).await
+        }
+
+        async fn request_scale_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            timeout: Duration,
+        ) -> Result<R, Error>
+        where
+            T: Encode,
+            R: Decode,
+        {
+            (**self)
+                .request_scale_with_timeout(path, body, timeout)
+                .await
+        }
+
+        async fn request_stream_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            timeout: Duration,
+        ) -> Result<ResponseStream<'_, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self)
+                .request_stream_with_timeout(path, body, timeout)
+                .await
+        }
+
+        async fn request_client_stream_with_timeout<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + 'a,
+            timeout: Duration,
+        ) -> Result<ResponseStream<'a, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self)
+                .request_client_stream_with_timeout(path, requests, timeout)
+                .await
+        }
+    }
+
+    impl<C: SendRequestClient + ?Sized> SendRequestClient for &C {
+        fn request<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request(path, body)
+        }
+
+        fn request_scale<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Encode + Send,
+            R: Decode,
+        {
+            (**self).request_scale(path, body)
+        }
+
+        fn request_stream<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> impl Future<Output = Result<SendResponseStream<'_, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_stream(path, body)
+        }
+
+        fn request_client_stream<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + Send + 'a,
+        ) -> impl Future<Output = Result<SendResponseStream<'a, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_client_stream(path, requests)
+        }
+
+        fn request_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            timeout: Duration,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_with_timeout(path, body, timeout)
+        }
+
+        fn request_scale_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            timeout: Duration,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Encode + Send,
+            R: Decode,
+        {
+            (**self).request_scale_with_timeout(path, body, timeout)
+        }
+
+        fn request_stream_with_timeout<T, R>(
+            &self,
+            path: &str,
+            body: T,
+            timeout: Duration,
+        ) -> impl Future<Output = Result<SendResponseStream<'_, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_stream_with_timeout(path, body, timeout)
+        }
+
+        fn request_client_stream_with_timeout<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + Send + 'a,
+            timeout: Duration,
+        ) -> impl Future<Output = Result<SendResponseStream<'a, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            (**self).request_client_stream_with_timeout(path, requests, timeout)
+        }
+    }
+
+    /// A transport sending the requests of `client` with `timeout`, if any, through the
+    /// `*_with_timeout` methods of [`RequestClient`] or [`SendRequestClient`].
+    ///
+    /// Returned, borrowing the transport, by the `with_timeout` method of the generated clients.
+    #[derive(Debug, Clone, Copy)]
+    pub struct WithTimeout<C> {
+        pub client: C,
+        pub timeout: Option<Duration>,
+    }
+
+    impl<C> WithTimeout<C> {
+        pub fn new(client: C, timeout: Option<Duration>) -> Self {
+            Self { client, timeout }
+        }
+    }
+
+    impl<C: RequestClient> RequestClient for WithTimeout<C> {
+        async fn request<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            match self.timeout {
+                Some(timeout) => self.client.request_with_timeout(path, body, timeout).await,
+                None => self.client.request(path, body).await,
+            }
+        }
+
+        async fn request_scale<T, R>(&self, path: &str, body: T) -> Result<R, Error>
+        where
+            T: Encode,
+            R: Decode,
+        {
+            match self.timeout {
+                Some(timeout) => {
+                    self.client
+                        .request_scale_with_timeout(path, body, timeout)
+                        .await
+                }
+                None => self.client.request_scale(path, body).await,
+            }
+        }
+
+        async fn request_stream<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> Result<ResponseStream<'_, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            match self.timeout {
+                Some(timeout) => {
+                    self.client
+                        .request_stream_with_timeout(path, body, timeout)
+                        .await
+                }
+                None => self.client.request_stream(path, body).await,
+            }
+        }
+
+        async fn request_client_stream<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + 'a,
+        ) -> Result<ResponseStream<'a, R>, Error>
+        where
+            T: Message + Serialize,
+            R: Message + DeserializeOwned + Default,
+        {
+            match self.timeout {
+                Some(timeout) => {
+                    self.client
+                        .request_client_stream_with_timeout(path, requests, timeout)
+                        .await
+                }
+                None => self.client.request_client_stream(path, requests).await,
+            }
+        }
+    }
+
+    impl<C: SendRequestClient> SendRequestClient for WithTimeout<C> {
+        fn request<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            match self.timeout {
+                Some(timeout) => {
+                    Either::Left(self.client.request_with_timeout(path, body, timeout))
+                }
+                None => Either::Right(self.client.request(path, body)),
+            }
+        }
+
+        fn request_scale<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> impl Future<Output = Result<R, Error>> + Send
+        where
+            T: Encode + Send,
+            R: Decode,
+        {
+            match self.timeout {
+                Some(timeout) => {
+                    Either::Left(self.client.request_scale_with_timeout(path, body, timeout))
+                }
+                None => Either::Right(self.client.request_scale(path, body)),
+            }
+        }
+
+        fn request_stream<T, R>(
+            &self,
+            path: &str,
+            body: T,
+        ) -> impl Future<Output = Result<SendResponseStream<'_, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            match self.timeout {
+                Some(timeout) => {
+                    Either::Left(self.client.request_stream_with_timeout(path, body, timeout))
+                }
+                None => Either::Right(self.client.request_stream(path, body)),
+            }
+        }
+
+        fn request_client_stream<'a, T, R>(
+            &'a self,
+            path: &str,
+            requests: impl Stream<Item = T> + Send + 'a,
+        ) -> impl Future<Output = Result<SendResponseStream<'a, R>, Error>> + Send
+        where
+            T: Message + Serialize + Send,
+            R: Message + DeserializeOwned + Default,
+        {
+            match self.timeout {
+                Some(timeout) => Either::Left(
+                    self.client
+                        .request_client_stream_with_timeout(path, requests, timeout),
+                ),
+                None => Either::Right(self.client.request_client_stream(path, requests)),
+            }
+        }
     }
 }
 
@@ -165,3 +1459,197 @@ pub mod codec {
         buf
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::server::{route_service, Code, Error, ProtoError};
+    use anyhow::Context;
+
+    #[test]
+    fn test_proto_error_from_error() {
+        let err: Error = ProtoError::with_code(Code::NotFound, "no such key").into();
+        let err = ProtoError::from(err.context("lookup failed"));
+        assert_eq!(err.code(), Code::NotFound);
+        assert_eq!(err.message, "no such key");
+        assert_eq!(err.http_status(), 404);
+
+        let err = ProtoError::from(
+            Err::<(), _>(anyhow::anyhow!("boom"))
+                .context("handler")
+                .unwrap_err(),
+        );
+        assert_eq!(err.code(), Code::Unknown);
+        assert_eq!(err.message, "handler: boom");
+        assert_eq!(err.http_status(), 500);
+    }
+
+    #[test]
+    fn test_proto_error_without_code() {
+        use prost::Message;
+
+        // An error encoded by a peer predating status codes only carries a message.
+        #[derive(Clone, PartialEq, Message)]
+        struct LegacyError {
+            #[prost(string, tag = "1")]
+            message: String,
+        }
+        let legacy = LegacyError {
+            message: "boom".into(),
+        };
+        let err = ProtoError::decode(&legacy.encode_to_vec()[..]).unwrap();
+        assert_eq!(err.message, "boom");
+        assert_eq!(err.code(), Code::Unknown);
+        assert_eq!(err.http_status(), 500);
+
+        // Codes are wire compatible with the enumeration.
+        let err = ProtoError::with_code(Code::NotFound, "missing");
+        let err = ProtoError::decode(&err.encode_to_vec()[..]).unwrap();
+        assert_eq!(err.code(), Code::NotFound);
+        let err = ProtoError::decode(&[0x10, 0x63][..]).unwrap();
+        assert_eq!(err.code(), Code::Unknown);
+    }
+
+    #[test]
+    fn test_route_service() {
+        let names = ["my.pkg.Echo", "my.pkg.EchoAdmin", "my.pkg.Echo.V2", "Plain"];
+        assert_eq!(route_service("my.pkg.Echo.Say", names), Some("my.pkg.Echo"));
+        assert_eq!(
+            route_service("my.pkg.EchoAdmin.Say", names),
+            Some("my.pkg.EchoAdmin")
+        );
+        assert_eq!(
+            route_service("my.pkg.Echo.V2.Say", names),
+            Some("my.pkg.Echo.V2")
+        );
+        assert_eq!(route_service("Plain.Say", names), Some("Plain"));
+        assert_eq!(route_service("my.pkg.Echo", names), None);
+        assert_eq!(route_service("my.pkg.Other.Say", names), None);
+        assert_eq!(route_service("Say", ["", "Plain"]), Some(""));
+    }
+
+    #[test]
+    fn test_limits() {
+        use super::server::{DispatchError, Limits};
+        use alloc::collections::BTreeMap;
+
+        let limits = Limits::new().with_max_body_size(4).with_max_repeated(2);
+        assert!(limits.check_size(4).is_ok());
+        assert!(matches!(
+            limits.check_size(5),
+            Err(DispatchError::LimitExceeded(_))
+        ));
+        assert!(limits.check(&[vec![1, 2], vec![3, 4]]).is_ok());
+        assert!(limits.check(&vec![vec![1, 2, 3]]).is_err());
+        assert!(limits
+            .check(&BTreeMap::from([(1, ()), (2, ()), (3, ())]))
+            .is_err());
+        assert!(Limits::new().check(&vec![0; 1000]).is_ok());
+        // The elements of bytes are not counted as repeated elements.
+        assert!(limits.check(&vec![0u8; 1000]).is_ok());
+        assert!(limits
+            .check(&vec![vec![0u8; 1000], vec![0u8; 1000]])
+            .is_ok());
+        assert!(limits
+            .check(&vec![vec![0u8; 1000], vec![], vec![]])
+            .is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_bytes_as_hex_str() {
+        use super::serde_helpers::{bytes_as_hex_str, vec_bytes_as_hex_str};
+        use super::server::Limits;
+
+        struct Hex(Vec<Vec<u8>>);
+
+        impl serde::Serialize for Hex {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                vec_bytes_as_hex_str::serialize(&self.0, serializer)
+            }
+        }
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        let mut json = Vec::new();
+        bytes_as_hex_str::serialize(&bytes, &mut serde_json::Serializer::new(&mut json)).unwrap();
+        assert_eq!(json, serde_json::to_vec(&hex::encode(&bytes)).unwrap());
+
+        let hex = Hex(vec![vec![0xab; 1000], vec![]]);
+        assert_eq!(
+            serde_json::to_string(&hex).unwrap(),
+            serde_json::to_string(&["ab".repeat(1000), String::new()]).unwrap()
+        );
+        assert!(Limits::new().with_max_repeated(2).check(&hex).is_ok());
+        assert!(Limits::new().with_max_repeated(1).check(&hex).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_context_timeout() {
+        use super::server::{format_timeout, parse_timeout, Context};
+        use core::time::Duration;
+
+        assert_eq!(parse_timeout(" 1500 "), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_timeout("1.5s"), None);
+        assert_eq!(format_timeout(Duration::from_micros(1500)), "2");
+
+        let context = Context::new()
+            .with_timeout(Duration::from_secs(60))
+            .with_timeout(Duration::from_secs(3600));
+        let remaining = context.remaining().unwrap();
+        assert!(remaining <= Duration::from_secs(60), "{:?}", remaining);
+        assert!(!context.is_expired());
+        assert!(Context::new().with_timeout(Duration::ZERO).is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_request_with_timeout() {
+        use super::client::{Error, RequestClient, WithTimeout};
+        use super::Message;
+        use core::cell::Cell;
+        use core::time::Duration;
+        use serde::{de::DeserializeOwned, Serialize};
+
+        // A transport implementing the original signature of `request`.
+        #[derive(Default)]
+        struct Recorder(Cell<Option<Option<Duration>>>);
+
+        impl RequestClient for Recorder {
+            async fn request<T, R>(&self, _path: &str, _body: T) -> Result<R, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned,
+            {
+                self.0.set(Some(None));
+                Ok(serde_json::from_str("null")?)
+            }
+
+            async fn request_with_timeout<T, R>(
+                &self,
+                _path: &str,
+                _body: T,
+                timeout: Duration,
+            ) -> Result<R, Error>
+            where
+                T: Message + Serialize,
+                R: Message + DeserializeOwned,
+            {
+                self.0.set(Some(Some(timeout)));
+                Ok(serde_json::from_str("null")?)
+            }
+        }
+
+        let transport = Recorder::default();
+        let () = WithTimeout::new(&transport, None)
+            .request("Echo.Say", ())
+            .await
+            .unwrap();
+        assert_eq!(transport.0.take(), Some(None));
+
+        let timeout = Duration::from_millis(100);
+        let () = WithTimeout::new(&transport, Some(timeout))
+            .request("Echo.Say", ())
+            .await
+            .unwrap();
+        assert_eq!(transport.0.take(), Some(Some(timeout)));
+    }
+}
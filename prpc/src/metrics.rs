@@ -0,0 +1,61 @@
+//! [`Metrics`] adapter for the [`metrics`](::metrics) crate facade, so calls are exported by any
+//! installed recorder, e.g. `metrics-exporter-prometheus`.
+//!
+//! [`MetricsAdapter`] records, labeled by `method` path (or `unknown` for paths the service does
+//! not serve) and `encoding`:
+//!
+//! - `prpc_requests_total`: counter of calls, also labeled by status `code`.
+//! - `prpc_request_errors_total`: counter of failed calls, also labeled by status `code`.
+//! - `prpc_request_duration_seconds`: histogram of call durations.
+//! - `prpc_request_size_bytes` and `prpc_response_size_bytes`: histograms of encoded sizes.
+//!
+//! ```ignore
+//! let metrics = MetricsMiddleware::new(MetricsAdapter::new());
+//! let service = MiddlewareService::new(metrics, EchoServer::new(app));
+//! ```
+
+use crate::server::{CallMetrics, Code, Metrics};
+use alloc::format;
+use alloc::string::String;
+
+/// A [`Metrics`] recording calls with the macros of the [`metrics`](::metrics) crate.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsAdapter {
+    prefix: Option<String>,
+}
+
+impl MetricsAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the `prpc` prefix of the metric names.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    fn name(&self, name: &str) -> String {
+        format!("{}_{name}", self.prefix.as_deref().unwrap_or("prpc"))
+    }
+}
+
+impl Metrics for MetricsAdapter {
+    fn record(&self, call: &CallMetrics<'_>) {
+        let method = ("method", String::from(call.path));
+        let encoding = ("encoding", format!("{:?}", call.encoding));
+        let labels = [method.clone(), encoding.clone()];
+        let labels_with_code = [method, encoding, ("code", format!("{:?}", call.code))];
+
+        ::metrics::counter!(self.name("requests_total"), &labels_with_code).increment(1);
+        if call.code != Code::Ok {
+            ::metrics::counter!(self.name("request_errors_total"), &labels_with_code).increment(1);
+        }
+        ::metrics::histogram!(self.name("request_duration_seconds"), &labels)
+            .record(call.duration.as_secs_f64());
+        ::metrics::histogram!(self.name("request_size_bytes"), &labels)
+            .record(call.request_size as f64);
+        ::metrics::histogram!(self.name("response_size_bytes"), &labels)
+            .record(call.response_size as f64);
+    }
+}
@@ -0,0 +1,380 @@
+//! Reflection service exposing the `file_descriptor_set.bin` written by `prpc-build`.
+//!
+//! Add [`ReflectionService`] to a [`ComposedService`](crate::server::ComposedService) to let
+//! generic tools discover the services, methods and message types of an API at runtime. The
+//! application decodes the descriptors once into [`Descriptors`] and exposes them through
+//! `AsRef`:
+//!
+//! ```ignore
+//! let descriptors = Descriptors::new(include_bytes!(concat!(
+//!     env!("OUT_DIR"),
+//!     "/file_descriptor_set.bin"
+//! )))?;
+//!
+//! impl AsRef<Descriptors> for App {
+//!     fn as_ref(&self) -> &Descriptors {
+//!         &self.descriptors
+//!     }
+//! }
+//!
+//! type Api = ComposedService<App, (EchoServer<App>, ReflectionService<App>)>;
+//! ```
+//!
+//! Method paths are reported as `{package}.{Service}.{Method}`, the default of `prpc-build`, or
+//! as configured with [`Descriptors::with_method_paths`].
+
+use crate::server::{
+    Code, Context, DispatchError, Encoding, MethodPaths, NamedService, ProtoError,
+    SendResponseStream, SendService, Service,
+};
+use crate::Message;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use prost_types::{DescriptorProto, FileDescriptorProto, FileDescriptorSet};
+use serde::{Deserialize, Serialize};
+
+/// The decoded `google.protobuf.FileDescriptorSet` served by a [`ReflectionService`].
+///
+/// Cloning yields a handle to the same descriptors.
+#[derive(Clone, Debug)]
+pub struct Descriptors {
+    inner: Arc<DescriptorsInner>,
+}
+
+#[derive(Clone, Debug)]
+struct DescriptorsInner {
+    files: Vec<FileDescriptorProto>,
+    paths: MethodPaths,
+}
+
+impl Descriptors {
+    /// Decode an encoded `FileDescriptorSet`, e.g. the `file_descriptor_set.bin` written by
+    /// `prpc-build`.
+    pub fn new(file_descriptor_set: &[u8]) -> Result<Self, prost::DecodeError> {
+        let files = FileDescriptorSet::decode(file_descriptor_set)?.file;
+        Ok(Self {
+            inner: Arc::new(DescriptorsInner {
+                files,
+                paths: MethodPaths::default(),
+            }),
+        })
+    }
+
+    /// Report the method paths of `paths`, matching servers generated with the same path
+    /// options of `prpc-build`.
+    pub fn with_method_paths(mut self, paths: &MethodPaths) -> Self {
+        Arc::make_mut(&mut self.inner).paths = paths.clone();
+        self
+    }
+
+    fn files(&self) -> &[FileDescriptorProto] {
+        &self.inner.files
+    }
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct ListServicesRequest {}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct ListServicesResponse {
+    /// Fully qualified names of the services, e.g. `my.pkg.Service`.
+    #[prost(string, repeated, tag = "1")]
+    #[serde(default)]
+    pub services: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct ListMethodsRequest {
+    /// Fully qualified name of the service.
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub service: String,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct MethodInfo {
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub name: String,
+    /// The path to dispatch requests to, e.g. `my.pkg.Service.Method`.
+    #[prost(string, tag = "2")]
+    #[serde(default)]
+    pub path: String,
+    /// Fully qualified name of the request message.
+    #[prost(string, tag = "3")]
+    #[serde(default)]
+    pub input_type: String,
+    /// Fully qualified name of the response message.
+    #[prost(string, tag = "4")]
+    #[serde(default)]
+    pub output_type: String,
+    #[prost(bool, tag = "5")]
+    #[serde(default)]
+    pub client_streaming: bool,
+    #[prost(bool, tag = "6")]
+    #[serde(default)]
+    pub server_streaming: bool,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct ListMethodsResponse {
+    #[prost(message, repeated, tag = "1")]
+    #[serde(default)]
+    pub methods: Vec<MethodInfo>,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct GetFileDescriptorRequest {
+    /// Fully qualified name of a service, method, message or enum.
+    #[prost(string, tag = "1")]
+    #[serde(default)]
+    pub symbol: String,
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct GetFileDescriptorResponse {
+    /// Encoded `google.protobuf.FileDescriptorProto`s of the file defining the symbol, followed
+    /// by its transitive dependencies.
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    #[serde(default, with = "crate::serde_helpers::vec_bytes_as_hex_str")]
+    pub file_descriptor_proto: Vec<Vec<u8>>,
+}
+
+/// The reflection service of some [`Descriptors`].
+///
+/// It can be constructed from any application type `A` exposing its descriptors via `AsRef`.
+pub struct ReflectionService<A = ()> {
+    descriptors: Descriptors,
+    _marker: PhantomData<fn() -> A>,
+}
+
+impl<A> ReflectionService<A> {
+    pub fn new(descriptors: Descriptors) -> Self {
+        Self {
+            descriptors,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A> Clone for ReflectionService<A> {
+    fn clone(&self) -> Self {
+        Self::new(self.descriptors.clone())
+    }
+}
+
+impl<A: AsRef<Descriptors>> From<A> for ReflectionService<A> {
+    fn from(app: A) -> Self {
+        Self::new(app.as_ref().clone())
+    }
+}
+
+impl<A> ReflectionService<A> {
+    pub fn list_services(&self) -> ListServicesResponse {
+        let services = self
+            .descriptors
+            .files()
+            .iter()
+            .flat_map(|file| {
+                file.service
+                    .iter()
+                    .map(move |service| qualified_name(file.package(), service.name()))
+            })
+            .collect();
+        ListServicesResponse { services }
+    }
+
+    pub fn list_methods(
+        &self,
+        request: ListMethodsRequest,
+    ) -> Result<ListMethodsResponse, ProtoError> {
+        let (file, service) = self
+            .descriptors
+            .files()
+            .iter()
+            .flat_map(|file| file.service.iter().map(move |service| (file, service)))
+            .find(|(file, service)| {
+                qualified_name(file.package(), service.name()) == request.service
+            })
+            .ok_or_else(|| {
+                ProtoError::with_code(
+                    Code::NotFound,
+                    alloc::format!("Service not found: {}", request.service),
+                )
+            })?;
+        let methods = service
+            .method
+            .iter()
+            .map(|method| MethodInfo {
+                name: method.name().into(),
+                path: self.descriptors.inner.paths.join(
+                    file.package(),
+                    service.name(),
+                    method.name(),
+                ),
+                input_type: method.input_type().trim_start_matches('.').into(),
+                output_type: method.output_type().trim_start_matches('.').into(),
+                client_streaming: method.client_streaming(),
+                server_streaming: method.server_streaming(),
+            })
+            .collect();
+        Ok(ListMethodsResponse { methods })
+    }
+
+    pub fn get_file_descriptor(
+        &self,
+        request: GetFileDescriptorRequest,
+    ) -> Result<GetFileDescriptorResponse, ProtoError> {
+        let files = self.descriptors.files();
+        let symbol = request.symbol.trim_start_matches('.');
+        let file = files
+            .iter()
+            .find(|file| defines_symbol(file, symbol))
+            .ok_or_else(|| {
+                ProtoError::with_code(Code::NotFound, alloc::format!("Symbol not found: {symbol}"))
+            })?;
+
+        let mut names: Vec<&str> = Vec::new();
+        let mut pending = Vec::from([file.name()]);
+        while let Some(name) = pending.pop() {
+            if names.contains(&name) {
+                continue;
+            }
+            names.push(name);
+            if let Some(file) = files.iter().find(|file| file.name() == name) {
+                pending.extend(file.dependency.iter().map(String::as_str));
+            }
+        }
+        let file_descriptor_proto = names
+            .into_iter()
+            .filter_map(|name| files.iter().find(|file| file.name() == name))
+            .map(Message::encode_to_vec)
+            .collect();
+        Ok(GetFileDescriptorResponse {
+            file_descriptor_proto,
+        })
+    }
+}
+
+fn qualified_name(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        alloc::format!("{scope}.{name}")
+    }
+}
+
+fn defines_symbol(file: &FileDescriptorProto, symbol: &str) -> bool {
+    let package = file.package();
+    let Some(name) = (if package.is_empty() {
+        Some(symbol)
+    } else {
+        symbol
+            .strip_prefix(package)
+            .and_then(|name| name.strip_prefix('.'))
+    }) else {
+        return false;
+    };
+    file.service.iter().any(|service| {
+        name == service.name()
+            || service
+                .method
+                .iter()
+                .any(|method| name == qualified_name(service.name(), method.name()))
+    }) || file.enum_type.iter().any(|ty| name == ty.name())
+        || file
+            .message_type
+            .iter()
+            .any(|message| message_defines_symbol(message, "", name))
+}
+
+fn message_defines_symbol(message: &DescriptorProto, scope: &str, name: &str) -> bool {
+    let message_name = qualified_name(scope, message.name());
+    name == message_name
+        || message
+            .enum_type
+            .iter()
+            .any(|ty| name == qualified_name(&message_name, ty.name()))
+        || message
+            .nested_type
+            .iter()
+            .any(|nested| message_defines_symbol(nested, &message_name, name))
+}
+
+impl<A> NamedService for ReflectionService<A> {
+    const NAME: &'static str = "prpc.reflection.Reflection";
+}
+
+impl<A> Service for ReflectionService<A> {
+    type Methods = &'static [&'static str];
+
+    fn methods() -> Self::Methods {
+        &[
+            "prpc.reflection.Reflection.ListServices",
+            "prpc.reflection.Reflection.ListMethods",
+            "prpc.reflection.Reflection.GetFileDescriptor",
+        ]
+    }
+
+    async fn dispatch_request(
+        self,
+        _context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        let data = data.as_ref();
+        let method = path
+            .strip_prefix(Self::NAME)
+            .and_then(|method| method.strip_prefix('.'));
+        match method {
+            Some("ListServices") => {
+                let _: ListServicesRequest = encoding.decode(data)?;
+                let response = self.list_services();
+                encoding.encode(&response)
+            }
+            Some("ListMethods") => {
+                let request = encoding.decode(data)?;
+                let response = self.list_methods(request).map_err(handler_error)?;
+                encoding.encode(&response)
+            }
+            Some("GetFileDescriptor") => {
+                let request = encoding.decode(data)?;
+                let response = self.get_file_descriptor(request).map_err(handler_error)?;
+                encoding.encode(&response)
+            }
+            _ => Err(DispatchError::MethodNotFound(path.into())),
+        }
+    }
+}
+
+impl<A> SendService for ReflectionService<A> {
+    async fn dispatch_request(
+        self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]> + Send,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        Service::dispatch_request(self, context, path, data, encoding).await
+    }
+
+    async fn dispatch_stream_request<'a>(
+        self,
+        _context: &'a Context,
+        path: &str,
+        _data: impl AsRef<[u8]> + Send,
+        _encoding: Encoding,
+    ) -> Result<SendResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        Err(DispatchError::MethodNotFound(path.into()))
+    }
+}
+
+fn handler_error(error: ProtoError) -> DispatchError {
+    DispatchError::Handler(error.into())
+}
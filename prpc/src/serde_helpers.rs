@@ -1,15 +1,41 @@
 pub use prpc_serde_bytes::prpc_serde_bytes;
 
+use core::fmt;
+
+/// Serializes bytes as a hex string written straight to the serializer with `collect_str`, so
+/// serializers able to stream it, e.g. `serde_json` or the request limits check, do not allocate
+/// the string.
+struct HexStr<'a>(&'a [u8]);
+
+impl fmt::Display for HexStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; 128];
+        for chunk in self.0.chunks(buf.len() / 2) {
+            let hex = &mut buf[..chunk.len() * 2];
+            hex::encode_to_slice(chunk, hex).map_err(|_| fmt::Error)?;
+            f.write_str(core::str::from_utf8(hex).map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl serde::Serialize for HexStr<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 pub mod bytes_as_hex_str {
     use alloc::string::String;
     use alloc::vec::Vec;
     use serde::{Deserialize, Serialize};
 
+    #[allow(clippy::ptr_arg)]
     pub fn serialize<S>(bytes: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        hex::encode(bytes).serialize(serializer)
+        super::HexStr(bytes).serialize(serializer)
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
@@ -25,15 +51,14 @@ pub mod bytes_as_hex_str {
 pub mod vec_bytes_as_hex_str {
     use alloc::string::String;
     use alloc::vec::Vec;
-    use serde::{Deserialize, Serialize};
+    use serde::Deserialize;
 
     #[allow(clippy::ptr_arg)]
     pub fn serialize<S>(bytes: &Vec<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let hex_strs: Vec<String> = bytes.iter().map(|bytes| hex::encode(bytes)).collect();
-        hex_strs.serialize(serializer)
+        serializer.collect_seq(bytes.iter().map(|bytes| super::HexStr(bytes)))
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
@@ -61,7 +86,7 @@ pub mod option_bytes_as_hex_str {
         S: serde::Serializer,
     {
         match bytes {
-            Some(bytes) => hex::encode(bytes).serialize(serializer),
+            Some(bytes) => super::HexStr(bytes).serialize(serializer),
             None => serializer.serialize_none(),
         }
     }
@@ -0,0 +1,52 @@
+use super::*;
+
+use core::future::Future;
+
+/// Checks the access policies declared on methods with `@auth <policy>` annotations.
+///
+/// The `{Service}Rpc` traits generated for services with annotated methods require this trait,
+/// and the generated servers call [`Authorizer::authorize`] before decoding the request.
+pub trait Authorizer {
+    /// Check that the caller described by `context` may call the method at `path`, which
+    /// requires `policy`.
+    ///
+    /// Errors are reported as [`DispatchError::PermissionDenied`]. Return a [`ProtoError`] to
+    /// choose another code, e.g. [`Code::Unauthenticated`].
+    async fn authorize(&self, context: &Context, path: &str, policy: &str) -> Result<(), Error>;
+}
+
+/// Like [`Authorizer`], with a `Send` future, required by the `{Service}Rpc` traits generated
+/// with `Builder::send_futures` of `prpc-build`.
+pub trait SendAuthorizer {
+    /// Check that the caller described by `context` may call the method at `path`, which
+    /// requires `policy`, see [`Authorizer::authorize`].
+    fn authorize(
+        &self,
+        context: &Context,
+        path: &str,
+        policy: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+macro_rules! forward_authorizer {
+    ($($pointer:ty),*) => {$(
+        impl<T: Authorizer + ?Sized> Authorizer for $pointer {
+            async fn authorize(&self, context: &Context, path: &str, policy: &str) -> Result<(), Error> {
+                T::authorize(self, context, path, policy).await
+            }
+        }
+
+        impl<T: SendAuthorizer + ?Sized> SendAuthorizer for $pointer {
+            fn authorize(
+                &self,
+                context: &Context,
+                path: &str,
+                policy: &str,
+            ) -> impl Future<Output = Result<(), Error>> + Send {
+                T::authorize(self, context, path, policy)
+            }
+        }
+    )*};
+}
+
+forward_authorizer!(&T, Box<T>, alloc::sync::Arc<T>);
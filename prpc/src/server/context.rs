@@ -0,0 +1,108 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::any::{Any, TypeId};
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Per-request information provided by the transport, such as the caller identity and request
+/// metadata.
+///
+/// Handlers receive it when the server is generated with `Builder::handler_context` enabled.
+#[derive(Debug, Default, Clone)]
+pub struct Context {
+    /// Identity of the caller, as authenticated by the transport.
+    pub caller: Option<String>,
+    /// Address of the remote peer.
+    pub remote_addr: Option<String>,
+    /// Id of the request, assigned by the client or the transport.
+    pub request_id: Option<String>,
+    /// Request metadata such as HTTP headers. Keys are lower case.
+    pub metadata: BTreeMap<String, String>,
+    /// Point in time after which the caller no longer waits for the response.
+    ///
+    /// Handlers can pass the [`remaining`](Context::remaining) time on to downstream calls.
+    #[cfg(feature = "std")]
+    pub deadline: Option<Instant>,
+    extensions: BTreeMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_caller(mut self, caller: impl Into<String>) -> Self {
+        self.caller = Some(caller.into());
+        self
+    }
+
+    pub fn with_remote_addr(mut self, remote_addr: impl Into<String>) -> Self {
+        self.remote_addr = Some(remote_addr.into());
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// Set the deadline, unless the current one is earlier.
+    #[cfg(feature = "std")]
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(match self.deadline {
+            Some(current) => current.min(deadline),
+            None => deadline,
+        });
+        self
+    }
+
+    /// Set the deadline to `timeout` from now, unless the current one is earlier.
+    #[cfg(feature = "std")]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self,
+        }
+    }
+
+    /// Time left until the deadline, if any. Zero once it has passed.
+    #[cfg(feature = "std")]
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether the deadline has passed.
+    #[cfg(feature = "std")]
+    pub fn is_expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    /// Add a metadata entry. The key is converted to lower case.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata
+            .insert(key.into().to_ascii_lowercase(), value.into());
+        self
+    }
+
+    /// Get a metadata entry by its case-insensitive key.
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .get(&key.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Attach a value of an arbitrary type, replacing any previous value of the same type.
+    pub fn insert_extension<T: Any + Send + Sync>(&mut self, value: T) {
+        self.extensions.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Get the attached value of type `T`.
+    pub fn extension<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.extensions.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+}
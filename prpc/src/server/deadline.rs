@@ -0,0 +1,250 @@
+use super::*;
+
+use alloc::string::String;
+use core::time::Duration;
+#[cfg(feature = "deadline")]
+use {core::future::Future, std::time::Instant};
+
+/// Request header, and [`Context::metadata`] key, carrying the timeout of a request in
+/// milliseconds, e.g. `prpc-timeout: 1500`.
+pub const TIMEOUT_HEADER: &str = "prpc-timeout";
+
+/// Parse a [`TIMEOUT_HEADER`] value.
+pub fn parse_timeout(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_millis)
+}
+
+/// Format `timeout` as a [`TIMEOUT_HEADER`] value, rounded up to whole milliseconds.
+///
+/// Clients forwarding the deadline of a request pass [`Context::remaining`].
+pub fn format_timeout(timeout: Duration) -> String {
+    alloc::format!("{}", timeout.as_nanos().div_ceil(1_000_000))
+}
+
+/// A [`Service`] wrapper failing requests with [`DispatchError::DeadlineExceeded`] once their
+/// deadline passes, dropping the handler future.
+///
+/// The deadline of a request is the earlier of [`Context::deadline`] and the timeout of the
+/// method, see [`Service::timeout_for`], or the default timeout of the wrapper for methods
+/// without one. Response streams end with a [`Code::DeadlineExceeded`] error if the deadline
+/// passes while streaming.
+///
+/// Handlers only see the deadlines set in the [`Context`] by the transport, e.g.
+/// [`TowerService`](crate::tower::TowerService) applies the [`TIMEOUT_HEADER`] and the method
+/// timeout. Must be polled within a tokio runtime with the time driver enabled.
+#[cfg(feature = "deadline")]
+#[derive(Debug, Clone)]
+pub struct DeadlineService<S> {
+    service: S,
+    default_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "deadline")]
+impl<S> DeadlineService<S> {
+    pub fn new(service: S) -> Self {
+        Self {
+            service,
+            default_timeout: None,
+        }
+    }
+
+    /// Time out methods without a [`Service::timeout_for`] after `timeout`.
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = Some(timeout);
+        self
+    }
+}
+
+#[cfg(feature = "deadline")]
+impl<S: Service> DeadlineService<S> {
+    fn deadline(&self, context: &Context, path: &str) -> Option<Instant> {
+        let method_deadline = self
+            .service
+            .timeout_for(path)
+            .or(self.default_timeout)
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+        match (context.deadline, method_deadline) {
+            (Some(deadline), Some(method_deadline)) => Some(deadline.min(method_deadline)),
+            (deadline, method_deadline) => deadline.or(method_deadline),
+        }
+    }
+}
+
+#[cfg(feature = "deadline")]
+async fn with_deadline<T>(
+    deadline: Option<Instant>,
+    future: impl Future<Output = Result<T, DispatchError>>,
+) -> Result<T, DispatchError> {
+    match deadline {
+        None => future.await,
+        Some(deadline) if deadline <= Instant::now() => Err(DispatchError::DeadlineExceeded),
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), future)
+            .await
+            .unwrap_or(Err(DispatchError::DeadlineExceeded)),
+    }
+}
+
+#[cfg(feature = "deadline")]
+fn stream_with_deadline(
+    stream: ResponseStream<'_>,
+    deadline: Option<Instant>,
+) -> ResponseStream<'_> {
+    match deadline {
+        Some(deadline) => Box::pin(expire_stream(stream, deadline)),
+        None => stream,
+    }
+}
+
+#[cfg(feature = "deadline")]
+fn send_stream_with_deadline(
+    stream: SendResponseStream<'_>,
+    deadline: Option<Instant>,
+) -> SendResponseStream<'_> {
+    match deadline {
+        Some(deadline) => Box::pin(expire_stream(stream, deadline)),
+        None => stream,
+    }
+}
+
+// End `stream` with a `DeadlineExceeded` error once `deadline` passes.
+#[cfg(feature = "deadline")]
+fn expire_stream<'a, S>(
+    stream: S,
+    deadline: Instant,
+) -> impl Stream<Item = Result<Vec<u8>, Error>> + 'a
+where
+    S: Stream<Item = Result<Vec<u8>, Error>> + Unpin + 'a,
+{
+    let sleep = Box::pin(tokio::time::sleep_until(deadline.into()));
+    futures_util::stream::unfold(Some((stream, sleep)), |state| async move {
+        let (mut stream, mut sleep) = state?;
+        tokio::select! {
+            item = stream.next() => item.map(|item| (item, Some((stream, sleep)))),
+            () = &mut sleep => {
+                let err = ProtoError::with_code(Code::DeadlineExceeded, "Deadline exceeded");
+                Some((Err(err.into()), None))
+            }
+        }
+    })
+}
+
+#[cfg(feature = "deadline")]
+impl<S: Service> Service for DeadlineService<S> {
+    type Methods = S::Methods;
+
+    fn methods() -> Self::Methods {
+        S::methods()
+    }
+
+    fn method_timeout(path: &str) -> Option<Duration> {
+        S::method_timeout(path)
+    }
+
+    fn timeout_for(&self, path: &str) -> Option<Duration> {
+        self.service.timeout_for(path)
+    }
+
+    async fn dispatch_request(
+        self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        let deadline = self.deadline(context, path);
+        with_deadline(
+            deadline,
+            self.service.dispatch_request(context, path, data, encoding),
+        )
+        .await
+    }
+
+    async fn dispatch_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        let deadline = self.deadline(context, path);
+        let stream = with_deadline(
+            deadline,
+            self.service
+                .dispatch_stream_request(context, path, data, encoding),
+        )
+        .await?;
+        Ok(stream_with_deadline(stream, deadline))
+    }
+
+    async fn dispatch_client_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        let deadline = self.deadline(context, path);
+        let stream = with_deadline(
+            deadline,
+            self.service
+                .dispatch_client_stream_request(context, path, requests, encoding),
+        )
+        .await?;
+        Ok(stream_with_deadline(stream, deadline))
+    }
+}
+
+// The futures are returned as `impl Future + Send` rather than by `async fn`, so that callers
+// spawning them do not have to prove them `Send` through the future of `S` (rust-lang/rust#100013).
+#[cfg(feature = "deadline")]
+#[allow(clippy::manual_async_fn)]
+impl<S: Service + SendService + Send> SendService for DeadlineService<S> {
+    fn dispatch_request(
+        self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]> + Send,
+        encoding: Encoding,
+    ) -> impl Future<Output = Result<Vec<u8>, DispatchError>> + Send {
+        async move {
+            let deadline = self.deadline(context, path);
+            with_deadline(
+                deadline,
+                SendService::dispatch_request(self.service, context, path, data, encoding),
+            )
+            .await
+        }
+    }
+
+    fn dispatch_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]> + Send,
+        encoding: Encoding,
+    ) -> impl Future<Output = Result<SendResponseStream<'a>, DispatchError>> + Send
+    where
+        Self: 'a,
+    {
+        async move {
+            let deadline = self.deadline(context, path);
+            let stream = with_deadline(
+                deadline,
+                SendService::dispatch_stream_request(self.service, context, path, data, encoding),
+            )
+            .await?;
+            Ok(send_stream_with_deadline(stream, deadline))
+        }
+    }
+}
+
+#[cfg(feature = "deadline")]
+impl<S: NamedService> NamedService for DeadlineService<S> {
+    const NAME: &'static str = S::NAME;
+}
@@ -0,0 +1,286 @@
+use super::*;
+
+use alloc::sync::Arc;
+use core::future::Future;
+use core::time::Duration;
+
+/// A boxed future, as returned by the object safe `Dyn` traits.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// A boxed `Send` future, as returned by the object safe `Dyn` traits generated with
+/// `Builder::send_futures` of `prpc-build`.
+pub type SendBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A boxed stream, as returned by the object safe `Dyn` traits.
+pub type BoxStream<'a, T> = Pin<Box<dyn Stream<Item = T> + 'a>>;
+
+/// A boxed `Send` stream, see [`SendBoxFuture`].
+pub type SendBoxStream<'a, T> = Pin<Box<dyn Stream<Item = T> + Send + 'a>>;
+
+/// Stream of encoded request frames passed to a [`DynService`].
+pub type FrameStream<'a> = BoxStream<'a, Result<Vec<u8>, Error>>;
+
+/// Object safe form of [`Service`], to store services of different types as
+/// `Arc<dyn DynService>` or choose them at runtime.
+///
+/// Implemented for every [`Service`] that is [`Clone`], cloning it for each request. In turn,
+/// `Box<dyn DynService>` and `Arc<dyn DynService>` implement [`Service`], with the timeouts of
+/// [`DynService::dyn_method_timeout`] as their [`Service::timeout_for`]. As their methods are only
+/// known at runtime, the static [`Service::methods`] is empty and [`Service::method_timeout`] is
+/// `None`, see [`DynService::dyn_methods`] instead. The methods are
+/// prefixed with `dyn_` so that calls to the [`Service`] methods of a service stay unambiguous.
+pub trait DynService {
+    /// The paths of the methods, see [`Service::methods`].
+    fn dyn_methods(&self) -> Vec<&'static str>;
+
+    /// See [`Service::method_timeout`].
+    fn dyn_method_timeout(&self, path: &str) -> Option<Duration>;
+
+    fn dyn_dispatch_request<'a>(
+        &'a self,
+        context: &'a Context,
+        path: &'a str,
+        data: &'a [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'a, Result<Vec<u8>, DispatchError>>;
+
+    /// See [`Service::dispatch_stream_request`]. The response stream only borrows the context.
+    fn dyn_dispatch_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        data: &'b [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b;
+
+    /// See [`Service::dispatch_client_stream_request`].
+    fn dyn_dispatch_client_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        requests: FrameStream<'a>,
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b;
+}
+
+impl<S: Service + Clone> DynService for S {
+    fn dyn_methods(&self) -> Vec<&'static str> {
+        S::methods().as_ref().to_vec()
+    }
+
+    fn dyn_method_timeout(&self, path: &str) -> Option<Duration> {
+        self.timeout_for(path)
+    }
+
+    fn dyn_dispatch_request<'a>(
+        &'a self,
+        context: &'a Context,
+        path: &'a str,
+        data: &'a [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'a, Result<Vec<u8>, DispatchError>> {
+        Box::pin(self.clone().dispatch_request(context, path, data, encoding))
+    }
+
+    fn dyn_dispatch_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        data: &'b [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b,
+    {
+        Box::pin(
+            self.clone()
+                .dispatch_stream_request(context, path, data, encoding),
+        )
+    }
+
+    fn dyn_dispatch_client_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        requests: FrameStream<'a>,
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b,
+    {
+        Box::pin(
+            self.clone()
+                .dispatch_client_stream_request(context, path, requests, encoding),
+        )
+    }
+}
+
+/// `Send` form of [`DynService`], so the calls of an `Arc<dyn SendDynService>` can be spawned on
+/// multi-threaded runtimes.
+///
+/// Implemented for every [`Service`] that is also a [`SendService`], [`Clone`], `Send` and
+/// `Sync`, e.g. the servers generated with `Builder::send_futures` of `prpc-build`. In turn,
+/// `Box<dyn SendDynService>` and `Arc<dyn SendDynService>` implement [`SendService`], and
+/// [`Service`] through [`DynService`].
+pub trait SendDynService: DynService + Send + Sync {
+    /// See [`SendService::dispatch_request`].
+    fn dyn_send_dispatch_request<'a>(
+        &'a self,
+        context: &'a Context,
+        path: &'a str,
+        data: &'a [u8],
+        encoding: Encoding,
+    ) -> SendBoxFuture<'a, Result<Vec<u8>, DispatchError>>;
+
+    /// See [`SendService::dispatch_stream_request`]. The response stream only borrows the
+    /// context.
+    fn dyn_send_dispatch_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        data: &'b [u8],
+        encoding: Encoding,
+    ) -> SendBoxFuture<'b, Result<SendResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b;
+}
+
+impl<S: Service + SendService + Clone + Send + Sync> SendDynService for S {
+    fn dyn_send_dispatch_request<'a>(
+        &'a self,
+        context: &'a Context,
+        path: &'a str,
+        data: &'a [u8],
+        encoding: Encoding,
+    ) -> SendBoxFuture<'a, Result<Vec<u8>, DispatchError>> {
+        Box::pin(SendService::dispatch_request(
+            self.clone(),
+            context,
+            path,
+            data,
+            encoding,
+        ))
+    }
+
+    fn dyn_send_dispatch_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        data: &'b [u8],
+        encoding: Encoding,
+    ) -> SendBoxFuture<'b, Result<SendResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b,
+    {
+        Box::pin(SendService::dispatch_stream_request(
+            self.clone(),
+            context,
+            path,
+            data,
+            encoding,
+        ))
+    }
+}
+
+// Implement `Service` for the pointers to a `DynService`.
+macro_rules! impl_service_for_dyn {
+    ($($pointer:ident),*) => {$(
+        impl<S: DynService + ?Sized> Service for $pointer<S> {
+            type Methods = Vec<&'static str>;
+
+            fn methods() -> Self::Methods {
+                Vec::new()
+            }
+
+            fn timeout_for(&self, path: &str) -> Option<Duration> {
+                (**self).dyn_method_timeout(path)
+            }
+
+            async fn dispatch_request(
+                self,
+                context: &Context,
+                path: &str,
+                data: impl AsRef<[u8]>,
+                encoding: Encoding,
+            ) -> Result<Vec<u8>, DispatchError> {
+                (*self).dyn_dispatch_request(context, path, data.as_ref(), encoding).await
+            }
+
+            async fn dispatch_stream_request<'a>(
+                self,
+                context: &'a Context,
+                path: &str,
+                data: impl AsRef<[u8]>,
+                encoding: Encoding,
+            ) -> Result<ResponseStream<'a>, DispatchError>
+            where
+                Self: 'a,
+            {
+                (*self).dyn_dispatch_stream_request(context, path, data.as_ref(), encoding).await
+            }
+
+            async fn dispatch_client_stream_request<'a>(
+                self,
+                context: &'a Context,
+                path: &str,
+                requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+                encoding: Encoding,
+            ) -> Result<ResponseStream<'a>, DispatchError>
+            where
+                Self: 'a,
+            {
+                (*self)
+                    .dyn_dispatch_client_stream_request(context, path, Box::pin(requests), encoding)
+                    .await
+            }
+        }
+    )*};
+}
+
+impl_service_for_dyn!(Box, Arc);
+
+// Implement `SendService` for the pointers to a `SendDynService`.
+macro_rules! impl_send_service_for_dyn {
+    ($($pointer:ident),*) => {$(
+        impl<S: SendDynService + ?Sized> SendService for $pointer<S> {
+            async fn dispatch_request(
+                self,
+                context: &Context,
+                path: &str,
+                data: impl AsRef<[u8]> + Send,
+                encoding: Encoding,
+            ) -> Result<Vec<u8>, DispatchError> {
+                (*self)
+                    .dyn_send_dispatch_request(context, path, data.as_ref(), encoding)
+                    .await
+            }
+
+            async fn dispatch_stream_request<'a>(
+                self,
+                context: &'a Context,
+                path: &str,
+                data: impl AsRef<[u8]> + Send,
+                encoding: Encoding,
+            ) -> Result<SendResponseStream<'a>, DispatchError>
+            where
+                Self: 'a,
+            {
+                (*self)
+                    .dyn_send_dispatch_stream_request(context, path, data.as_ref(), encoding)
+                    .await
+            }
+        }
+    )*};
+}
+
+impl_send_service_for_dyn!(Box, Arc);
@@ -0,0 +1,116 @@
+use super::{decode_json, decode_message, encode_json, DispatchError};
+use alloc::vec::Vec;
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Wire encoding of the request and response bodies of a dispatched call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// Protobuf messages.
+    #[default]
+    Protobuf,
+    /// JSON messages.
+    Json,
+    /// Requests are URL query strings, responses are JSON.
+    Query,
+    /// SCALE encoded messages. Only supported by servers generated with SCALE encoding enabled.
+    Scale,
+    /// CBOR messages.
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// MessagePack messages.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    /// The content type of response bodies in this encoding.
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Protobuf => "application/x-protobuf",
+            Encoding::Json | Encoding::Query => "application/json",
+            Encoding::Scale => "application/x-scale",
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => "application/cbor",
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// The encoding of request bodies with the given content type, ignoring parameters such as
+    /// `charset`.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        let is_any = |candidates: &[&str]| {
+            candidates
+                .iter()
+                .any(|candidate| essence.eq_ignore_ascii_case(candidate))
+        };
+        if is_any(&["application/json"]) {
+            return Some(Encoding::Json);
+        }
+        if is_any(&["application/x-protobuf", "application/protobuf"]) {
+            return Some(Encoding::Protobuf);
+        }
+        if is_any(&["application/x-scale", "application/scale"]) {
+            return Some(Encoding::Scale);
+        }
+        #[cfg(feature = "cbor")]
+        if is_any(&["application/cbor"]) {
+            return Some(Encoding::Cbor);
+        }
+        #[cfg(feature = "msgpack")]
+        if is_any(&[
+            "application/msgpack",
+            "application/x-msgpack",
+            "application/vnd.msgpack",
+        ]) {
+            return Some(Encoding::MessagePack);
+        }
+        None
+    }
+
+    /// Whether response bodies in this encoding are JSON.
+    pub fn is_json(self) -> bool {
+        matches!(self, Encoding::Json | Encoding::Query)
+    }
+
+    /// Decode a request message. An empty body in a serde based encoding decodes to the default
+    /// message.
+    ///
+    /// SCALE is not supported here as it needs [`Decode`](parity_scale_codec::Decode) messages,
+    /// see [`decode_scale`](super::decode_scale).
+    pub fn decode<M>(self, data: &[u8]) -> Result<M, DispatchError>
+    where
+        M: Message + DeserializeOwned + Default,
+    {
+        match self {
+            Encoding::Protobuf => decode_message(data),
+            Encoding::Json => decode_json(data, false),
+            Encoding::Query => decode_json(data, true),
+            Encoding::Scale => Err(DispatchError::UnsupportedEncoding(self)),
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => super::decode_cbor(data),
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => super::decode_msgpack(data),
+        }
+    }
+
+    /// Encode a response message. SCALE is not supported, see
+    /// [`encode_scale`](super::encode_scale).
+    pub fn encode<M>(self, message: &M) -> Result<Vec<u8>, DispatchError>
+    where
+        M: Message + Serialize,
+    {
+        match self {
+            Encoding::Protobuf => Ok(message.encode_to_vec()),
+            Encoding::Json | Encoding::Query => encode_json(message),
+            Encoding::Scale => Err(DispatchError::UnsupportedEncoding(self)),
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => super::encode_cbor(message),
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => super::encode_msgpack(message),
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use super::*;
+
+use alloc::string::{String, ToString};
+use core::fmt;
+use serde::ser::{self, Serialize};
+
+/// Limits on the requests decoded by a generated server, reported as
+/// [`DispatchError::LimitExceeded`].
+///
+/// The body size is checked before decoding. The other limits are checked after decoding, on the
+/// decoded message by walking its serde representation, so messages must implement `Serialize`
+/// and the whole message is already allocated when they are exceeded: set a `max_body_size` to
+/// bound that. Methods can override the
+/// limits of their server with `@limit <name> <value>` annotations, e.g.
+/// `@limit max_body_size 65536`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum size of an encoded request message, in bytes.
+    pub max_body_size: Option<usize>,
+    /// Maximum nesting depth of messages, the request message being at depth 1.
+    pub max_depth: Option<usize>,
+    /// Maximum number of elements of each repeated or map field.
+    pub max_repeated: Option<usize>,
+}
+
+impl Limits {
+    /// No limits.
+    pub const fn new() -> Self {
+        Self {
+            max_body_size: None,
+            max_depth: None,
+            max_repeated: None,
+        }
+    }
+
+    pub const fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub const fn with_max_repeated(mut self, max_repeated: usize) -> Self {
+        self.max_repeated = Some(max_repeated);
+        self
+    }
+
+    /// Check the size of an encoded request.
+    pub fn check_size(&self, size: usize) -> Result<(), DispatchError> {
+        match self.max_body_size {
+            Some(max) if size > max => Err(DispatchError::LimitExceeded(alloc::format!(
+                "request of {size} bytes exceeds the maximum of {max}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the nesting depth and repeated fields of a decoded request.
+    ///
+    /// Bytes and string fields are skipped without being encoded, e.g. the hex strings of the
+    /// [`serde_helpers`](crate::serde_helpers) are not formatted. Bytes serialized as a sequence of
+    /// `u8`, like a plain `Vec<u8>`, are not counted as repeated elements.
+    pub fn check<M: Serialize>(&self, message: &M) -> Result<(), DispatchError> {
+        if self.max_depth.is_none() && self.max_repeated.is_none() {
+            return Ok(());
+        }
+        message
+            .serialize(Measure {
+                limits: self,
+                depth: 0,
+            })
+            .map(|_| ())
+            .map_err(|LimitError(message)| DispatchError::LimitExceeded(message))
+    }
+}
+
+/// What a value serialized as, so the `u8` elements of bytes are not counted as repeated
+/// elements.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Byte,
+    Other,
+}
+
+#[derive(Debug)]
+struct LimitError(String);
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl core::error::Error for LimitError {}
+
+impl ser::Error for LimitError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        LimitError(msg.to_string())
+    }
+}
+
+/// A serializer discarding the message, only checking its shape against the limits.
+#[derive(Clone, Copy)]
+struct Measure<'a> {
+    limits: &'a Limits,
+    depth: usize,
+}
+
+impl<'a> Measure<'a> {
+    fn enter_message(self) -> Result<Self, LimitError> {
+        let depth = self.depth + 1;
+        match self.limits.max_depth {
+            Some(max) if depth > max => Err(LimitError(alloc::format!(
+                "message nesting depth exceeds the maximum of {max}"
+            ))),
+            _ => Ok(Measure { depth, ..self }),
+        }
+    }
+
+    // The length is not checked upfront, as the elements of bytes are not counted.
+    fn repeated(self) -> Repeated<'a> {
+        Repeated {
+            measure: self,
+            count: 0,
+        }
+    }
+}
+
+/// The elements of a repeated or map field.
+struct Repeated<'a> {
+    measure: Measure<'a>,
+    count: usize,
+}
+
+impl Repeated<'_> {
+    fn count(&mut self) -> Result<(), LimitError> {
+        self.count += 1;
+        match self.measure.limits.max_repeated {
+            Some(max) if self.count > max => Err(LimitError(alloc::format!(
+                "repeated field exceeds the maximum of {max} elements"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+macro_rules! measure_scalars {
+    ($($method:ident($ty:ty),)*) => {$(
+        fn $method(self, _v: $ty) -> Result<Shape, LimitError> {
+            Ok(Shape::Other)
+        }
+    )*};
+}
+
+impl<'a> ser::Serializer for Measure<'a> {
+    type Ok = Shape;
+    type Error = LimitError;
+    type SerializeSeq = Repeated<'a>;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Repeated<'a>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    measure_scalars! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Shape, LimitError> {
+        Ok(Shape::Byte)
+    }
+
+    fn collect_str<T: fmt::Display + ?Sized>(self, _value: &T) -> Result<Shape, LimitError> {
+        Ok(Shape::Other)
+    }
+
+    fn serialize_none(self) -> Result<Shape, LimitError> {
+        Ok(Shape::Other)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Shape, LimitError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Shape, LimitError> {
+        Ok(Shape::Other)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Shape, LimitError> {
+        Ok(Shape::Other)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Shape, LimitError> {
+        Ok(Shape::Other)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Shape, LimitError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<Shape, LimitError> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Repeated<'a>, LimitError> {
+        Ok(self.repeated())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, LimitError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, LimitError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, LimitError> {
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Repeated<'a>, LimitError> {
+        Ok(self.repeated())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, LimitError> {
+        self.enter_message()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, LimitError> {
+        self.enter_message()
+    }
+}
+
+impl ser::SerializeSeq for Repeated<'_> {
+    type Ok = Shape;
+    type Error = LimitError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LimitError> {
+        match value.serialize(self.measure)? {
+            Shape::Byte => Ok(()),
+            Shape::Other => self.count(),
+        }
+    }
+
+    fn end(self) -> Result<Shape, LimitError> {
+        Ok(Shape::Other)
+    }
+}
+
+impl ser::SerializeMap for Repeated<'_> {
+    type Ok = Shape;
+    type Error = LimitError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), LimitError> {
+        key.serialize(self.measure)?;
+        self.count()
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LimitError> {
+        value.serialize(self.measure).map(|_| ())
+    }
+
+    fn end(self) -> Result<Shape, LimitError> {
+        Ok(Shape::Other)
+    }
+}
+
+macro_rules! measure_fields {
+    ($($trait:ident::$method:ident($($name:ident)?),)*) => {$(
+        impl ser::$trait for Measure<'_> {
+            type Ok = Shape;
+            type Error = LimitError;
+
+            fn $method<T: Serialize + ?Sized>(
+                &mut self,
+                $($name: &'static str,)?
+                value: &T,
+            ) -> Result<(), LimitError> {
+                value.serialize(*self).map(|_| ())
+            }
+
+            fn end(self) -> Result<Shape, LimitError> {
+                Ok(Shape::Other)
+            }
+        }
+    )*};
+}
+
+measure_fields! {
+    SerializeTuple::serialize_element(),
+    SerializeTupleStruct::serialize_field(),
+    SerializeTupleVariant::serialize_field(),
+    SerializeStruct::serialize_field(_key),
+    SerializeStructVariant::serialize_field(_key),
+}
@@ -0,0 +1,242 @@
+use super::*;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context as TaskContext, Poll};
+use core::time::Duration;
+use std::time::Instant;
+
+/// The [`CallMetrics::path`] of the calls to methods the service does not serve.
+pub const UNKNOWN_METHOD: &str = "unknown";
+
+/// Measurements of a dispatched call, reported to [`Metrics::record`].
+#[derive(Debug, Clone)]
+pub struct CallMetrics<'a> {
+    /// The method path, e.g. `my.pkg.Service.Method`, or [`UNKNOWN_METHOD`] for paths the service
+    /// does not serve, so that callers can not create a metric label per path.
+    pub path: &'a str,
+    pub encoding: Encoding,
+    /// Total size of the encoded request frames, in bytes.
+    pub request_size: usize,
+    /// Total size of the encoded response frames, in bytes.
+    pub response_size: usize,
+    /// [`Code::Ok`] on success, [`Code::Cancelled`] for response streams dropped before their end.
+    pub code: Code,
+    /// Time from dispatch to the response, or to the end of the response stream.
+    pub duration: Duration,
+}
+
+/// Receives the [`CallMetrics`] of the calls dispatched through a [`MetricsMiddleware`], e.g. to
+/// count requests and errors or to record latency and size histograms per method.
+pub trait Metrics {
+    fn record(&self, call: &CallMetrics<'_>);
+}
+
+impl<M: Metrics + ?Sized> Metrics for Arc<M> {
+    fn record(&self, call: &CallMetrics<'_>) {
+        (**self).record(call)
+    }
+}
+
+/// A [`Middleware`] reporting the [`CallMetrics`] of every call to `M`.
+///
+/// ```ignore
+/// let service = MiddlewareService::new(MetricsMiddleware::new(my_metrics), EchoServer::new(app));
+/// ```
+pub struct MetricsMiddleware<M> {
+    metrics: Arc<M>,
+}
+
+impl<M> Clone for MetricsMiddleware<M> {
+    fn clone(&self) -> Self {
+        Self {
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<M: Metrics> MetricsMiddleware<M> {
+    pub fn new(metrics: M) -> Self {
+        Self {
+            metrics: Arc::new(metrics),
+        }
+    }
+}
+
+/// A call whose response stream has not ended yet.
+struct PendingCall<M: Metrics> {
+    metrics: Arc<M>,
+    path: String,
+    encoding: Encoding,
+    request_size: Arc<AtomicUsize>,
+    response_size: usize,
+    code: Code,
+    start: Instant,
+}
+
+impl<M: Metrics> PendingCall<M> {
+    fn record(self) {
+        self.metrics.record(&CallMetrics {
+            path: &self.path,
+            encoding: self.encoding,
+            request_size: self.request_size.load(Ordering::Relaxed),
+            response_size: self.response_size,
+            code: self.code,
+            duration: self.start.elapsed(),
+        });
+    }
+}
+
+/// Records the pending call once the response stream ends or is dropped.
+struct RecordedStream<'a, M: Metrics> {
+    stream: ResponseStream<'a>,
+    call: Option<PendingCall<M>>,
+}
+
+impl<M: Metrics> Stream for RecordedStream<'_, M> {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = core::task::ready!(this.stream.as_mut().poll_next(cx));
+        match (&item, this.call.as_mut()) {
+            (Some(Ok(frame)), Some(call)) => call.response_size += frame.len(),
+            (Some(Err(err)), Some(call)) if call.code == Code::Ok => {
+                call.code = error_code(err).unwrap_or(Code::Unknown);
+            }
+            (None, _) => {
+                if let Some(call) = this.call.take() {
+                    call.record();
+                }
+            }
+            _ => {}
+        }
+        Poll::Ready(item)
+    }
+}
+
+impl<M: Metrics> Drop for RecordedStream<'_, M> {
+    fn drop(&mut self) {
+        if let Some(mut call) = self.call.take() {
+            if call.code == Code::Ok {
+                call.code = Code::Cancelled;
+            }
+            call.record();
+        }
+    }
+}
+
+// The recorded path of a call to `path`. Services dispatching to methods only known at runtime,
+// e.g. a `DynService`, have no `Service::methods`, their unknown paths are found by the error.
+fn method_label<'p, S: Service>(path: &'p str, error: Option<&DispatchError>) -> &'p str {
+    let methods = S::methods();
+    let known = if methods.as_ref().is_empty() {
+        !matches!(
+            error,
+            Some(DispatchError::ServiceNotFound(_) | DispatchError::MethodNotFound(_))
+        )
+    } else {
+        methods.as_ref().contains(&path)
+    };
+    if known {
+        path
+    } else {
+        UNKNOWN_METHOD
+    }
+}
+
+impl<M: Metrics> MetricsMiddleware<M> {
+    fn pending(&self, request_size: Arc<AtomicUsize>, encoding: Encoding) -> PendingCall<M> {
+        PendingCall {
+            metrics: self.metrics.clone(),
+            path: String::new(),
+            encoding,
+            request_size,
+            response_size: 0,
+            code: Code::Ok,
+            start: Instant::now(),
+        }
+    }
+
+    fn record_stream<'a, S: Service>(
+        &self,
+        mut pending: PendingCall<M>,
+        path: &str,
+        result: Result<ResponseStream<'a>, DispatchError>,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        M: 'a,
+    {
+        pending.path = method_label::<S>(path, result.as_ref().err()).into();
+        match result {
+            Ok(stream) => Ok(Box::pin(RecordedStream {
+                stream,
+                call: Some(pending),
+            })),
+            Err(err) => {
+                PendingCall {
+                    code: err.code(),
+                    ..pending
+                }
+                .record();
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<M: Metrics + 'static> Middleware for MetricsMiddleware<M> {
+    async fn dispatch_request<S: Service>(
+        &self,
+        service: S,
+        context: &Context,
+        request: Request<'_>,
+    ) -> Result<Vec<u8>, DispatchError> {
+        let start = Instant::now();
+        let result = request.dispatch(service, context).await;
+        self.metrics.record(&CallMetrics {
+            path: method_label::<S>(request.path, result.as_ref().err()),
+            encoding: request.encoding,
+            request_size: request.data.len(),
+            response_size: result.as_ref().map_or(0, Vec::len),
+            code: result.as_ref().err().map_or(Code::Ok, DispatchError::code),
+            duration: start.elapsed(),
+        });
+        result
+    }
+
+    async fn dispatch_stream_request<'a, S: Service + 'a>(
+        &self,
+        service: S,
+        context: &'a Context,
+        request: Request<'_>,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        let request_size = Arc::new(AtomicUsize::new(request.data.len()));
+        let pending = self.pending(request_size, request.encoding);
+        let path = request.path;
+        let result = request.dispatch_stream(service, context).await;
+        self.record_stream::<S>(pending, path, result)
+    }
+
+    async fn dispatch_client_stream_request<'a, S: Service + 'a>(
+        &self,
+        service: S,
+        context: &'a Context,
+        path: &str,
+        requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        let request_size = Arc::new(AtomicUsize::new(0));
+        let pending = self.pending(request_size.clone(), encoding);
+        let requests = requests.inspect(move |frame| {
+            if let Ok(frame) = frame {
+                request_size.fetch_add(frame.len(), Ordering::Relaxed);
+            }
+        });
+        let result = service
+            .dispatch_client_stream_request(context, path, requests, encoding)
+            .await;
+        self.record_stream::<S>(pending, path, result)
+    }
+}
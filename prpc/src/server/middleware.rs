@@ -0,0 +1,231 @@
+use super::*;
+
+use alloc::sync::Arc;
+
+/// A unary or server-streaming request as seen by a [`Middleware`].
+#[derive(Debug, Clone, Copy)]
+pub struct Request<'a> {
+    /// The method path, e.g. `my.pkg.Service.Method`.
+    pub path: &'a str,
+    /// The encoded request body.
+    pub data: &'a [u8],
+    /// The encoding of the request body and of the response.
+    pub encoding: Encoding,
+}
+
+impl Request<'_> {
+    /// Dispatch the request as a unary request to `service`.
+    pub async fn dispatch<S: Service>(
+        self,
+        service: S,
+        context: &Context,
+    ) -> Result<Vec<u8>, DispatchError> {
+        service
+            .dispatch_request(context, self.path, self.data, self.encoding)
+            .await
+    }
+
+    /// Dispatch the request as a server-streaming request to `service`.
+    pub async fn dispatch_stream<'a, S: Service + 'a>(
+        self,
+        service: S,
+        context: &'a Context,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        service
+            .dispatch_stream_request(context, self.path, self.data, self.encoding)
+            .await
+    }
+}
+
+/// Hook around the dispatch of requests to a [`Service`], for concerns such as logging, auth,
+/// rate limiting or metrics.
+///
+/// Each method receives the inner service and decides whether to dispatch the request to it,
+/// so it can inspect the request and response bytes or short-circuit with an error. The default
+/// implementations pass requests through unchanged.
+///
+/// ```ignore
+/// struct RequireCaller;
+///
+/// impl Middleware for RequireCaller {
+///     async fn dispatch_request<S: Service>(
+///         &self,
+///         service: S,
+///         context: &Context,
+///         request: Request<'_>,
+///     ) -> Result<Vec<u8>, DispatchError> {
+///         if context.caller.is_none() {
+///             let err = ProtoError::with_code(Code::Unauthenticated, "anonymous caller");
+///             return Err(DispatchError::Handler(err.into()));
+///         }
+///         request.dispatch(service, context).await
+///     }
+/// }
+///
+/// let service = MiddlewareService::new(RequireCaller, EchoServer::new(app));
+/// ```
+pub trait Middleware {
+    async fn dispatch_request<S: Service>(
+        &self,
+        service: S,
+        context: &Context,
+        request: Request<'_>,
+    ) -> Result<Vec<u8>, DispatchError> {
+        request.dispatch(service, context).await
+    }
+
+    async fn dispatch_stream_request<'a, S: Service + 'a>(
+        &self,
+        service: S,
+        context: &'a Context,
+        request: Request<'_>,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        request.dispatch_stream(service, context).await
+    }
+
+    /// Client-streaming and bidirectional requests. Only the request frames carry the body.
+    async fn dispatch_client_stream_request<'a, S: Service + 'a>(
+        &self,
+        service: S,
+        context: &'a Context,
+        path: &str,
+        requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        service
+            .dispatch_client_stream_request(context, path, requests, encoding)
+            .await
+    }
+}
+
+macro_rules! impl_middleware_for_pointer {
+    ($($pointer:ty),*) => {$(
+        impl<M: Middleware> Middleware for $pointer {
+            async fn dispatch_request<S: Service>(
+                &self,
+                service: S,
+                context: &Context,
+                request: Request<'_>,
+            ) -> Result<Vec<u8>, DispatchError> {
+                (**self).dispatch_request(service, context, request).await
+            }
+
+            async fn dispatch_stream_request<'a, S: Service + 'a>(
+                &self,
+                service: S,
+                context: &'a Context,
+                request: Request<'_>,
+            ) -> Result<ResponseStream<'a>, DispatchError> {
+                (**self)
+                    .dispatch_stream_request(service, context, request)
+                    .await
+            }
+
+            async fn dispatch_client_stream_request<'a, S: Service + 'a>(
+                &self,
+                service: S,
+                context: &'a Context,
+                path: &str,
+                requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+                encoding: Encoding,
+            ) -> Result<ResponseStream<'a>, DispatchError> {
+                (**self)
+                    .dispatch_client_stream_request(service, context, path, requests, encoding)
+                    .await
+            }
+        }
+    )*};
+}
+
+impl_middleware_for_pointer!(&M, Arc<M>);
+
+/// A [`Service`] whose requests pass through a [`Middleware`].
+///
+/// Middleware services can be nested to apply several middlewares, the outermost one seeing
+/// requests first.
+#[derive(Debug, Clone)]
+pub struct MiddlewareService<M, S> {
+    middleware: M,
+    service: S,
+}
+
+impl<M, S> MiddlewareService<M, S> {
+    pub fn new(middleware: M, service: S) -> Self {
+        Self {
+            middleware,
+            service,
+        }
+    }
+}
+
+impl<M: Middleware, S: Service> Service for MiddlewareService<M, S> {
+    type Methods = S::Methods;
+
+    fn methods() -> Self::Methods {
+        S::methods()
+    }
+
+    fn method_timeout(path: &str) -> Option<core::time::Duration> {
+        S::method_timeout(path)
+    }
+
+    fn timeout_for(&self, path: &str) -> Option<core::time::Duration> {
+        self.service.timeout_for(path)
+    }
+
+    async fn dispatch_request(
+        self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        let request = Request {
+            path,
+            data: data.as_ref(),
+            encoding,
+        };
+        self.middleware
+            .dispatch_request(self.service, context, request)
+            .await
+    }
+
+    async fn dispatch_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        let request = Request {
+            path,
+            data: data.as_ref(),
+            encoding,
+        };
+        self.middleware
+            .dispatch_stream_request(self.service, context, request)
+            .await
+    }
+
+    async fn dispatch_client_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError>
+    where
+        Self: 'a,
+    {
+        self.middleware
+            .dispatch_client_stream_request(self.service, context, path, requests, encoding)
+            .await
+    }
+}
+
+impl<M: Middleware, S: NamedService> NamedService for MiddlewareService<M, S> {
+    const NAME: &'static str = S::NAME;
+}
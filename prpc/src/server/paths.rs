@@ -0,0 +1,65 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// How the method paths of a service are formed from its package, service and method names,
+/// mirroring the path options of the `prpc-build` `Builder`.
+///
+/// Services built from descriptors at runtime use it to serve the same paths as the servers
+/// generated with those options. The default paths are `{package}.{Service}.{Method}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodPaths {
+    emit_package: bool,
+    emit_service_name: bool,
+    keep_service_names: Vec<String>,
+}
+
+impl Default for MethodPaths {
+    fn default() -> Self {
+        Self {
+            emit_package: true,
+            emit_service_name: true,
+            keep_service_names: Vec::new(),
+        }
+    }
+}
+
+impl MethodPaths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Omit the package, see `Builder::disable_package_emission`.
+    pub fn disable_package_emission(mut self) -> Self {
+        self.emit_package = false;
+        self
+    }
+
+    /// Omit the service name, see `Builder::disable_service_name_emission`.
+    pub fn disable_service_name_emission(mut self) -> Self {
+        self.emit_service_name = false;
+        self
+    }
+
+    /// Keep the name of the service `name` despite `disable_service_name_emission`, see
+    /// `Builder::keep_service_name`.
+    pub fn keep_service_name(mut self, name: impl Into<String>) -> Self {
+        self.keep_service_names.push(name.into());
+        self
+    }
+
+    /// The path of `method` of `service` in `package`, using the proto names. An empty `method`
+    /// gives the path prefix of the service.
+    pub fn join(&self, package: &str, service: &str, method: &str) -> String {
+        let mut parts = Vec::new();
+        if self.emit_package {
+            parts.push(package);
+        }
+        if self.emit_service_name || self.keep_service_names.iter().any(|name| name == service) {
+            parts.push(service);
+        }
+        if !method.is_empty() {
+            parts.push(method);
+        }
+        parts.join(".")
+    }
+}
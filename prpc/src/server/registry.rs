@@ -0,0 +1,229 @@
+use super::*;
+
+use alloc::sync::Arc;
+use core::time::Duration;
+use std::collections::HashMap;
+
+/// A [`DynService`] creating a new service with `F` for every request.
+struct MakeService<F>(F);
+
+impl<S, F> DynService for MakeService<F>
+where
+    S: Service + 'static,
+    F: Fn() -> S,
+{
+    fn dyn_methods(&self) -> Vec<&'static str> {
+        S::methods().as_ref().to_vec()
+    }
+
+    fn dyn_method_timeout(&self, path: &str) -> Option<Duration> {
+        (self.0)().timeout_for(path)
+    }
+
+    fn dyn_dispatch_request<'a>(
+        &'a self,
+        context: &'a Context,
+        path: &'a str,
+        data: &'a [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'a, Result<Vec<u8>, DispatchError>> {
+        Box::pin((self.0)().dispatch_request(context, path, data, encoding))
+    }
+
+    fn dyn_dispatch_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        data: &'b [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b,
+    {
+        Box::pin((self.0)().dispatch_stream_request(context, path, data, encoding))
+    }
+
+    fn dyn_dispatch_client_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        requests: FrameStream<'a>,
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b,
+    {
+        Box::pin((self.0)().dispatch_client_stream_request(context, path, requests, encoding))
+    }
+}
+
+type SharedService = Arc<dyn DynService + Send + Sync>;
+
+/// A set of services registered at runtime.
+///
+/// Unlike [`ComposedService`], the number of services is not limited and each service can carry
+/// its own state. A request is routed with a single hash lookup on its method path.
+///
+/// The registry is a [`DynService`], so `Arc<ServiceRegistry>` is a [`Service`] that can be served
+/// or wrapped like any other, e.g. by [`MiddlewareService`] or `TowerService`, which apply the
+/// method timeouts of the registered services through [`Service::timeout_for`]. As with any
+/// [`DynService`], the static [`Service::methods`] is then empty, see
+/// [`ServiceRegistry::methods`] instead.
+///
+/// ```ignore
+/// let state = Arc::new(AppState::default());
+/// let mut registry = ServiceRegistry::new();
+/// registry.register({
+///     let state = state.clone();
+///     move || EchoServer::new(state.clone())
+/// });
+/// let response = registry
+///     .dispatch_request(&Context::new(), "echo.Echo.Say", body, Encoding::Protobuf)
+///     .await?;
+/// ```
+#[derive(Default, Clone)]
+pub struct ServiceRegistry {
+    services: HashMap<&'static str, SharedService>,
+    methods: HashMap<&'static str, SharedService>,
+}
+
+impl ServiceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a service under its [`NamedService::NAME`].
+    ///
+    /// `make_service` is called to create the service for each request, typically wrapping a
+    /// shared handle to the application state. Registering a name twice replaces the earlier
+    /// service.
+    pub fn register<S, F>(&mut self, make_service: F) -> &mut Self
+    where
+        S: NamedService + 'static,
+        F: Fn() -> S + Send + Sync + 'static,
+    {
+        let service: SharedService = Arc::new(MakeService(make_service));
+        if let Some(replaced) = self.services.insert(S::NAME, service.clone()) {
+            self.methods
+                .retain(|_, service| !Arc::ptr_eq(service, &replaced));
+        }
+        for method in S::methods().as_ref() {
+            self.methods.insert(method, service.clone());
+        }
+        self
+    }
+
+    /// Names of the registered services.
+    pub fn services(&self) -> Vec<&'static str> {
+        self.services.keys().copied().collect()
+    }
+
+    /// Method paths of all registered services.
+    pub fn methods(&self) -> Vec<&'static str> {
+        self.methods.keys().copied().collect()
+    }
+
+    /// The default timeout of the method at `path`, see [`Service::method_timeout`].
+    pub fn method_timeout(&self, path: &str) -> Option<Duration> {
+        self.methods.get(path)?.dyn_method_timeout(path)
+    }
+
+    fn lookup(
+        &self,
+        path: &str,
+    ) -> Result<&(dyn DynService + Send + Sync + 'static), DispatchError> {
+        match self.methods.get(path) {
+            Some(service) => Ok(&**service),
+            None if route_service(path, self.services.keys().copied()).is_some() => {
+                Err(DispatchError::MethodNotFound(path.into()))
+            }
+            None => Err(DispatchError::ServiceNotFound(path.into())),
+        }
+    }
+
+    pub async fn dispatch_request(
+        &self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<Vec<u8>, DispatchError> {
+        self.lookup(path)?
+            .dyn_dispatch_request(context, path, data.as_ref(), encoding)
+            .await
+    }
+
+    pub async fn dispatch_stream_request<'a>(
+        &self,
+        context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]>,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        self.lookup(path)?
+            .dyn_dispatch_stream_request(context, path, data.as_ref(), encoding)
+            .await
+    }
+
+    pub async fn dispatch_client_stream_request<'a>(
+        &self,
+        context: &'a Context,
+        path: &str,
+        requests: impl Stream<Item = Result<Vec<u8>, Error>> + 'a,
+        encoding: Encoding,
+    ) -> Result<ResponseStream<'a>, DispatchError> {
+        self.lookup(path)?
+            .dyn_dispatch_client_stream_request(context, path, Box::pin(requests), encoding)
+            .await
+    }
+}
+
+impl DynService for ServiceRegistry {
+    fn dyn_methods(&self) -> Vec<&'static str> {
+        self.methods()
+    }
+
+    fn dyn_method_timeout(&self, path: &str) -> Option<Duration> {
+        self.method_timeout(path)
+    }
+
+    fn dyn_dispatch_request<'a>(
+        &'a self,
+        context: &'a Context,
+        path: &'a str,
+        data: &'a [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'a, Result<Vec<u8>, DispatchError>> {
+        Box::pin(self.dispatch_request(context, path, data, encoding))
+    }
+
+    fn dyn_dispatch_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        data: &'b [u8],
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b,
+    {
+        Box::pin(self.dispatch_stream_request(context, path, data, encoding))
+    }
+
+    fn dyn_dispatch_client_stream_request<'a, 'b>(
+        &'b self,
+        context: &'a Context,
+        path: &'b str,
+        requests: FrameStream<'a>,
+        encoding: Encoding,
+    ) -> BoxFuture<'b, Result<ResponseStream<'a>, DispatchError>>
+    where
+        Self: 'a,
+        'a: 'b,
+    {
+        Box::pin(self.dispatch_client_stream_request(context, path, requests, encoding))
+    }
+}
@@ -0,0 +1,48 @@
+use super::*;
+
+use core::future::Future;
+
+/// Stream of encoded response frames of a [`SendService`].
+pub type SendResponseStream<'a> = Pin<Box<dyn Stream<Item = Result<Vec<u8>, Error>> + Send + 'a>>;
+
+/// Like [`Service`], with `Send` dispatch futures and response streams so calls can be spawned
+/// on multi-threaded runtimes.
+///
+/// Implemented, along with [`Service`], by the servers generated with `Builder::send_futures`
+/// of `prpc-build`, and by the services wrapping them: [`ComposedService`], `DeadlineService`,
+/// the health and reflection services, and the pointers to a [`SendDynService`]. The futures of
+/// a [`Middleware`] and of the services registered in a `ServiceRegistry` are not `Send`, so
+/// [`MiddlewareService`] and `ServiceRegistry` only implement [`Service`].
+///
+/// Requests of client-streaming and bidirectional methods are not `Send`, so they are only
+/// dispatched by [`Service::dispatch_client_stream_request`].
+pub trait SendService {
+    fn dispatch_request(
+        self,
+        context: &Context,
+        path: &str,
+        data: impl AsRef<[u8]> + Send,
+        encoding: Encoding,
+    ) -> impl Future<Output = Result<Vec<u8>, DispatchError>> + Send;
+
+    /// Dispatch a server-streaming request, returning the stream of encoded response frames.
+    fn dispatch_stream_request<'a>(
+        self,
+        context: &'a Context,
+        path: &str,
+        data: impl AsRef<[u8]> + Send,
+        encoding: Encoding,
+    ) -> impl Future<Output = Result<SendResponseStream<'a>, DispatchError>> + Send
+    where
+        Self: Sized + 'a;
+}
+
+/// Encode each message of a `Send` response stream with `encode` and box it into a
+/// [`SendResponseStream`].
+pub fn encode_send_response_stream<'a, S, M, F>(stream: S, mut encode: F) -> SendResponseStream<'a>
+where
+    S: Stream<Item = Result<M, Error>> + Send + 'a,
+    F: FnMut(M) -> Result<Vec<u8>, Error> + Send + 'a,
+{
+    Box::pin(stream.map(move |item| item.and_then(&mut encode)))
+}
@@ -0,0 +1,420 @@
+//! [`tower_service::Service`] adapter exposing a prpc [`Service`] over HTTP.
+//!
+//! Requests are mapped onto [`Service::dispatch_request`] as follows:
+//!
+//! - The URI path, with the configured prefix stripped, is the method path.
+//! - `GET` requests decode the URI query string as the request ([`Encoding::Query`]).
+//! - Other requests decode the body in the [`Encoding`] of their content type, defaulting to
+//!   protobuf. Content types without an [`Encoding`] are rejected with
+//!   [`DispatchError::UnsupportedContentType`].
+//! - Responses are in the encoding of the request, JSON for `GET` requests. The `Accept` header
+//!   is not consulted.
+//! - The [`TIMEOUT_HEADER`] and the timeout of the method, see [`Service::timeout_for`], set the
+//!   [`Context::deadline`]. Enforcing it is up to the service, e.g. a
+//!   [`DeadlineService`](crate::server::DeadlineService).
+//!
+//! Failed requests respond with the status code of the [`DispatchError`] and a [`ProtoError`]
+//! body in the encoding of the request.
+//!
+//! The futures of a [`TowerService`] are not `Send`. For a [`SendService`], a
+//! [`SendTowerService`] responds with `Send` futures, as required to mount it in an `axum`
+//! router or to serve it on spawned `hyper` connections.
+
+use crate::server::{
+    encode_scale, parse_timeout, Context, DispatchError, Encoding, ProtoError, SendService,
+    Service, TIMEOUT_HEADER,
+};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes};
+use core::convert::Infallible;
+use core::fmt::Display;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context as TaskContext, Poll};
+use http::{header, HeaderValue, Method, Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, Full};
+
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+/// A boxed `Send` future, as returned by a [`SendTowerService`].
+pub type SendBoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// A [`tower_service::Service`] dispatching HTTP requests to a prpc [`Service`].
+///
+/// The prpc service is cloned for every request, so it should be cheap to clone, e.g. a
+/// [`ComposedService`](crate::server::ComposedService) over shared application state.
+///
+/// If the request carries a [`Context`] extension, e.g. inserted by an authentication layer, it
+/// is passed on to the service with the request headers added as metadata.
+///
+/// The response futures are not `Send`, even for a [`SendService`], so connections must be
+/// driven on their task rather than spawned. Convert it into a [`SendTowerService`] to serve a
+/// [`SendService`] with `Send` futures.
+#[derive(Debug, Clone)]
+pub struct TowerService<S> {
+    service: S,
+    prefix: Arc<str>,
+    max_body_size: Option<usize>,
+}
+
+impl<S> TowerService<S> {
+    pub fn new(service: S) -> Self {
+        Self {
+            service,
+            prefix: "/".into(),
+            max_body_size: None,
+        }
+    }
+
+    /// Only serve URI paths starting with `prefix`, e.g. `/prpc/`. Defaults to `/`.
+    pub fn with_prefix(mut self, prefix: impl AsRef<str>) -> Self {
+        self.prefix = prefix.as_ref().into();
+        self
+    }
+
+    /// Fail requests with a body larger than `max_body_size` bytes with
+    /// [`DispatchError::LimitExceeded`], without buffering the whole body.
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+}
+
+/// A [`TowerService`] of a [`SendService`], responding with `Send` futures.
+///
+/// Requests are dispatched with [`SendService::dispatch_request`], as described in the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct SendTowerService<S> {
+    inner: TowerService<S>,
+}
+
+impl<S> SendTowerService<S> {
+    pub fn new(service: S) -> Self {
+        TowerService::new(service).into()
+    }
+}
+
+impl<S> From<TowerService<S>> for SendTowerService<S> {
+    fn from(inner: TowerService<S>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, B> tower_service::Service<Request<B>> for TowerService<S>
+where
+    S: Service + Clone + 'static,
+    B: Body + 'static,
+    B::Error: Display,
+{
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let service = self.service.clone();
+        let prefix = self.prefix.clone();
+        let max_body_size = self.max_body_size;
+        Box::pin(
+            async move { Ok(dispatch_limited(service, &prefix, max_body_size, request).await) },
+        )
+    }
+}
+
+/// Dispatch an HTTP request to `service`, serving method paths under `prefix`.
+pub async fn dispatch<S, B>(service: S, prefix: &str, request: Request<B>) -> Response<Full<Bytes>>
+where
+    S: Service,
+    B: Body,
+    B::Error: Display,
+{
+    dispatch_limited(service, prefix, None, request).await
+}
+
+async fn dispatch_limited<S, B>(
+    service: S,
+    prefix: &str,
+    max_body_size: Option<usize>,
+    request: Request<B>,
+) -> Response<Full<Bytes>>
+where
+    S: Service,
+    B: Body,
+    B::Error: Display,
+{
+    let encoding = match request_encoding(&request) {
+        Ok(encoding) => encoding,
+        Err(err) => return error_response(err, Encoding::default()),
+    };
+    match dispatch_inner(service, prefix, max_body_size, request, encoding).await {
+        Ok(body) => response(StatusCode::OK, encoding, body),
+        Err(err) => error_response(err, encoding),
+    }
+}
+
+impl<S, B> tower_service::Service<Request<B>> for SendTowerService<S>
+where
+    S: Service + SendService + Clone + Send + Sync + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Display,
+{
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = SendBoxFuture<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let service = self.inner.service.clone();
+        let prefix = self.inner.prefix.clone();
+        let max_body_size = self.inner.max_body_size;
+        Box::pin(async move {
+            let encoding = match request_encoding(&request) {
+                Ok(encoding) => encoding,
+                Err(err) => return Ok(error_response(err, Encoding::default())),
+            };
+            let result = match read_request(&service, &prefix, max_body_size, request, encoding)
+                .await
+            {
+                Ok((context, path, data)) => {
+                    SendService::dispatch_request(service, &context, &path, data, encoding).await
+                }
+                Err(err) => Err(err),
+            };
+            Ok(match result {
+                Ok(body) => response(StatusCode::OK, encoding, body),
+                Err(err) => error_response(err, encoding),
+            })
+        })
+    }
+}
+
+async fn dispatch_inner<S, B>(
+    service: S,
+    prefix: &str,
+    max_body_size: Option<usize>,
+    request: Request<B>,
+    encoding: Encoding,
+) -> Result<Vec<u8>, DispatchError>
+where
+    S: Service,
+    B: Body,
+    B::Error: Display,
+{
+    let (context, path, data) =
+        read_request(&service, prefix, max_body_size, request, encoding).await?;
+    service
+        .dispatch_request(&context, &path, data, encoding)
+        .await
+}
+
+/// Read the [`Context`], method path and request data of an HTTP request to `service`.
+async fn read_request<S, B>(
+    service: &S,
+    prefix: &str,
+    max_body_size: Option<usize>,
+    request: Request<B>,
+    encoding: Encoding,
+) -> Result<(Context, String, Bytes), DispatchError>
+where
+    S: Service,
+    B: Body,
+    B::Error: Display,
+{
+    let mut context = request_context(&request);
+    let (parts, body) = request.into_parts();
+    let path = parts
+        .uri
+        .path()
+        .strip_prefix(prefix)
+        .ok_or_else(|| DispatchError::ServiceNotFound(parts.uri.path().into()))?;
+    if let Some(timeout) = service.timeout_for(path) {
+        context = context.with_timeout(timeout);
+    }
+    let path = String::from(path);
+    let data = if encoding == Encoding::Query {
+        Bytes::copy_from_slice(parts.uri.query().unwrap_or_default().as_bytes())
+    } else {
+        collect_body(body, max_body_size).await?
+    };
+    Ok((context, path, data))
+}
+
+async fn collect_body<B>(body: B, max_body_size: Option<usize>) -> Result<Bytes, DispatchError>
+where
+    B: Body,
+    B::Error: Display,
+{
+    let body_error = |err: B::Error| DispatchError::Decode(anyhow::anyhow!("{err}"));
+    let Some(max_body_size) = max_body_size else {
+        return Ok(body.collect().await.map_err(body_error)?.to_bytes());
+    };
+    let mut body = core::pin::pin!(body);
+    let mut data = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let Ok(chunk) = frame.map_err(body_error)?.into_data() else {
+            continue;
+        };
+        if data.len() + chunk.remaining() > max_body_size {
+            return Err(DispatchError::LimitExceeded(alloc::format!(
+                "request body exceeds the maximum of {max_body_size} bytes"
+            )));
+        }
+        data.put(chunk);
+    }
+    Ok(data.into())
+}
+
+/// The [`Encoding`] of a request: [`Encoding::Query`] for `GET` requests, otherwise the encoding
+/// of its content type, defaulting to [`Encoding::Protobuf`] without one.
+///
+/// Fails with [`DispatchError::UnsupportedContentType`] if the content type has no [`Encoding`].
+pub fn request_encoding<B>(request: &Request<B>) -> Result<Encoding, DispatchError> {
+    if request.method() == Method::GET {
+        return Ok(Encoding::Query);
+    }
+    let Some(content_type) = request.headers().get(header::CONTENT_TYPE) else {
+        return Ok(Encoding::default());
+    };
+    let content_type = String::from_utf8_lossy(content_type.as_bytes());
+    Encoding::from_content_type(&content_type)
+        .ok_or_else(|| DispatchError::UnsupportedContentType(content_type.into()))
+}
+
+/// Build the [`Context`] of a request from its [`Context`] extension and headers, including the
+/// deadline of the [`TIMEOUT_HEADER`].
+pub fn request_context<B>(request: &Request<B>) -> Context {
+    let mut context = request
+        .extensions()
+        .get::<Context>()
+        .cloned()
+        .unwrap_or_default();
+    for (name, value) in request.headers() {
+        if let Ok(value) = value.to_str() {
+            context.metadata.insert(name.as_str().into(), value.into());
+        }
+    }
+    if context.request_id.is_none() {
+        context.request_id = context.metadata("x-request-id").map(String::from);
+    }
+    match context.metadata(TIMEOUT_HEADER).and_then(parse_timeout) {
+        Some(timeout) => context.with_timeout(timeout),
+        None => context,
+    }
+}
+
+/// Build the error response of a failed request.
+pub fn error_response(error: DispatchError, encoding: Encoding) -> Response<Full<Bytes>> {
+    let status =
+        StatusCode::from_u16(error.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let error = ProtoError::from(error);
+    let body = match encoding {
+        Encoding::Scale => encode_scale(&error),
+        _ => encoding.encode(&error).unwrap_or_default(),
+    };
+    response(status, encoding, body)
+}
+
+fn response(status: StatusCode, encoding: Encoding, body: Vec<u8>) -> Response<Full<Bytes>> {
+    let content_type = encoding.content_type();
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::SendResponseStream;
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service for Echo {
+        type Methods = &'static [&'static str];
+
+        fn methods() -> Self::Methods {
+            &["Echo.Say"]
+        }
+
+        async fn dispatch_request(
+            self,
+            context: &Context,
+            path: &str,
+            data: impl AsRef<[u8]>,
+            encoding: Encoding,
+        ) -> Result<Vec<u8>, DispatchError> {
+            SendService::dispatch_request(self, context, path, data.as_ref().to_vec(), encoding)
+                .await
+        }
+    }
+
+    impl SendService for Echo {
+        fn dispatch_request(
+            self,
+            _context: &Context,
+            path: &str,
+            data: impl AsRef<[u8]> + Send,
+            _encoding: Encoding,
+        ) -> impl Future<Output = Result<Vec<u8>, DispatchError>> + Send {
+            let result = match path {
+                "Echo.Say" => Ok(data.as_ref().to_vec()),
+                _ => Err(DispatchError::MethodNotFound(path.into())),
+            };
+            async move { result }
+        }
+
+        async fn dispatch_stream_request<'a>(
+            self,
+            _context: &'a Context,
+            path: &str,
+            _data: impl AsRef<[u8]> + Send,
+            _encoding: Encoding,
+        ) -> Result<SendResponseStream<'a>, DispatchError>
+        where
+            Self: 'a,
+        {
+            Err(DispatchError::MethodNotFound(path.into()))
+        }
+    }
+
+    fn post(path: &str, body: &'static str) -> Request<Full<Bytes>> {
+        Request::post(path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from_static(body.as_bytes())))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_tower_service() {
+        let mut service = SendTowerService::from(TowerService::new(Echo).with_prefix("/prpc/"));
+        let response = tower_service::Service::call(&mut service, post("/prpc/Echo.Say", "{}"));
+        // The response future can be spawned.
+        let response = tokio::spawn(response).await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "{}");
+
+        let response = tower_service::Service::call(&mut service, post("/prpc/Echo.No", "{}"));
+        let response = tokio::spawn(response).await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = tower_service::Service::call(&mut service, post("/other", "{}"));
+        let response = tokio::spawn(response).await.unwrap().unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
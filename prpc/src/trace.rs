@@ -0,0 +1,106 @@
+//! [`tracing`] instrumentation of the code generated with `Builder::enable_tracing` of
+//! `prpc-build`.
+//!
+//! Every dispatched call runs in a `prpc.dispatch` span and every client call in a
+//! `prpc.request` span, with the fields `service`, `path` and, when known, `encoding` and
+//! `request_size`. Dispatch spans also carry the [`Context::request_id`], if any. Failed calls
+//! are recorded as events in their span, with the status `code` and the `error`.
+
+use crate::server::{Context, DispatchError, Encoding, ResponseStream, SendResponseStream};
+use alloc::boxed::Box;
+use core::fmt::Display;
+use core::future::Future;
+use futures_util::StreamExt;
+use tracing::{Instrument, Span};
+
+pub use tracing;
+
+/// The span of a call dispatched by a generated server.
+pub fn dispatch_span(
+    service: &str,
+    path: &str,
+    context: &Context,
+    encoding: Encoding,
+    request_size: Option<usize>,
+) -> Span {
+    tracing::info_span!(
+        "prpc.dispatch",
+        service,
+        path,
+        request_id = context.request_id.as_deref(),
+        encoding = ?encoding,
+        request_size
+    )
+}
+
+/// The span of a call of a generated client. The request size is the protobuf encoded length,
+/// or the SCALE encoded length for SCALE requests.
+pub fn request_span(service: &str, path: &str, request_size: Option<usize>) -> Span {
+    tracing::info_span!("prpc.request", service, path, request_size)
+}
+
+/// Record a failed dispatch as an event in the current span.
+pub fn record_dispatch_error(error: &DispatchError) {
+    let code = error.code();
+    match error {
+        DispatchError::Decode(_) => tracing::warn!(?code, %error, "failed to decode request"),
+        DispatchError::Handler(_) => tracing::warn!(?code, %error, "handler failed"),
+        DispatchError::Encode(_) => tracing::error!(?code, %error, "failed to encode response"),
+        _ => tracing::info!(?code, %error, "request rejected"),
+    }
+}
+
+/// Run the dispatch of a unary call in `span`, recording its failure.
+pub async fn dispatch<T>(
+    span: Span,
+    future: impl Future<Output = Result<T, DispatchError>>,
+) -> Result<T, DispatchError> {
+    let result = future.instrument(span.clone()).await;
+    if let Err(error) = &result {
+        span.in_scope(|| record_dispatch_error(error));
+    }
+    result
+}
+
+/// Run the dispatch of a streaming call in `span`, recording its failure and the errors of the
+/// response stream.
+pub async fn dispatch_stream<'a>(
+    span: Span,
+    future: impl Future<Output = Result<ResponseStream<'a>, DispatchError>>,
+) -> Result<ResponseStream<'a>, DispatchError> {
+    let stream = dispatch(span.clone(), future).await?;
+    Ok(Box::pin(stream.map(record_frame_error(span))))
+}
+
+/// Like [`dispatch_stream`], for the `Send` response streams of a
+/// [`SendService`](crate::server::SendService).
+pub async fn dispatch_send_stream<'a>(
+    span: Span,
+    future: impl Future<Output = Result<SendResponseStream<'a>, DispatchError>>,
+) -> Result<SendResponseStream<'a>, DispatchError> {
+    let stream = dispatch(span.clone(), future).await?;
+    Ok(Box::pin(stream.map(record_frame_error(span))))
+}
+
+fn record_frame_error<T, E: Display>(span: Span) -> impl FnMut(Result<T, E>) -> Result<T, E> {
+    move |frame| {
+        if let Err(error) = &frame {
+            span.in_scope(
+                || tracing::warn!(error = %format_args!("{error:#}"), "response stream failed"),
+            );
+        }
+        frame
+    }
+}
+
+/// Run a client call in `span`, recording its failure.
+pub async fn request<T, E: Display>(
+    span: Span,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let result = future.instrument(span.clone()).await;
+    if let Err(error) = &result {
+        span.in_scope(|| tracing::warn!(error = %format_args!("{error:#}"), "request failed"));
+    }
+    result
+}